@@ -0,0 +1,47 @@
+//! Helpers for the user-supplied embedding callbacks
+
+use pyo3::prelude::*;
+
+/// Infer the vector dimension an embedding callback produces
+///
+/// Calls the callback once with `sample_text` and returns the length of the
+/// vector, e.g. `VectorStore(infer_dimension(embed, "hello"))`, so the store
+/// dimension always matches the model.
+///
+/// Args:
+///     embedding_callback: Python callable that takes content and returns a
+///         vector, as passed to `VectorStore.set`
+///     sample_text: Text to embed (default: "hello")
+///
+/// Returns:
+///     Length of the produced vector
+///
+/// Raises:
+///     RuntimeError: If the callback raises (the original error is the cause)
+///     TypeError: If the callback does not return a sequence of floats
+///     ValueError: If the callback returns an empty vector
+#[pyfunction]
+#[pyo3(signature = (embedding_callback, sample_text = "hello"))]
+pub(crate) fn infer_dimension(py: Python, embedding_callback: Py<PyAny>, sample_text: &str) -> PyResult<usize> {
+    let output = embedding_callback.call1(py, (sample_text,)).map_err(|e| {
+        let err = PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "embedding_callback failed on the sample text: {}",
+            e
+        ));
+        err.set_cause(py, Some(e));
+        err
+    })?;
+
+    let vector: Vec<f32> = output.extract(py).map_err(|_| {
+        PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+            "embedding_callback must return a sequence of floats, got {}",
+            output.bind(py).get_type().name().map(|n| n.to_string()).unwrap_or_default()
+        ))
+    })?;
+    if vector.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "embedding_callback returned an empty vector",
+        ));
+    }
+    Ok(vector.len())
+}
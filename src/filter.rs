@@ -0,0 +1,209 @@
+//! Metadata filters written as Python dicts
+//!
+//! The syntax follows the MongoDB query style:
+//!
+//! ```text
+//! {"author": "alice"}                          equality
+//! {"year": {"$gte": 2020, "$lt": 2024}}        operators, combined with AND
+//! {"$or": [{"lang": "en"}, {"lang": "de"}]}    logical operators
+//! ```
+//!
+//! Supported operators: `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`,
+//! `$nin`, `$contains`, `$startswith`, plus `$and`, `$or` and `$not`.
+//! Filters are parsed into vecstore's `FilterExpr` and evaluated with its
+//! filter engine, so a record without the field never matches a comparison.
+//! Parsing also checks operand types (`$in` takes a list, `$gt` a number or
+//! string, ...), so a malformed filter fails up front with a ValueError.
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::Value;
+use vecstore::store::filters::evaluate_filter;
+use vecstore::{FilterExpr, FilterOp, Metadata};
+
+use crate::py_to_json;
+
+fn invalid(message: String) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid filter: {}", message))
+}
+
+fn comparison_op(name: &str) -> PyResult<FilterOp> {
+    Ok(match name {
+        "$eq" => FilterOp::Eq,
+        "$ne" => FilterOp::Neq,
+        "$gt" => FilterOp::Gt,
+        "$gte" => FilterOp::Gte,
+        "$lt" => FilterOp::Lt,
+        "$lte" => FilterOp::Lte,
+        "$in" => FilterOp::In,
+        "$nin" => FilterOp::NotIn,
+        "$contains" => FilterOp::Contains,
+        "$startswith" => FilterOp::StartsWith,
+        other => return Err(invalid(format!("unknown operator '{}'", other))),
+    })
+}
+
+/// Reject operands whose type can never match under the given operator
+fn check_operand(name: &str, op: &FilterOp, value: &Value) -> PyResult<()> {
+    let expected = match op {
+        FilterOp::In | FilterOp::NotIn if !value.is_array() => "a list",
+        FilterOp::Gt | FilterOp::Gte | FilterOp::Lt | FilterOp::Lte
+            if !(value.is_number() || value.is_string()) =>
+        {
+            "a number or string"
+        }
+        FilterOp::StartsWith if !value.is_string() => "a string",
+        FilterOp::Contains if value.is_array() || value.is_object() => "a scalar",
+        _ => return Ok(()),
+    };
+    Err(invalid(format!("'{}' expects {}, got {}", name, expected, value)))
+}
+
+/// Parse the sub-filters of `$and` / `$or`
+fn parse_list(name: &str, value: &Bound<'_, PyAny>) -> PyResult<Vec<FilterExpr>> {
+    let list = value
+        .cast::<PyList>()
+        .map_err(|_| invalid(format!("'{}' expects a list of filters", name)))?;
+    list.iter()
+        .map(|item| {
+            let dict = item
+                .cast::<PyDict>()
+                .map_err(|_| invalid(format!("'{}' expects a list of filters", name)))?;
+            parse_filter(dict)
+        })
+        .collect()
+}
+
+/// Parse the condition on a single field
+fn parse_field(field: &str, condition: &Bound<'_, PyAny>) -> PyResult<FilterExpr> {
+    let cmp = |name: &str, op, value: &Bound<'_, PyAny>| -> PyResult<FilterExpr> {
+        let value = py_to_json(value)?;
+        check_operand(name, &op, &value)?;
+        Ok(FilterExpr::Cmp {
+            field: field.to_string(),
+            op,
+            value,
+        })
+    };
+
+    // A dict of operators, e.g. {"$gte": 1}; any other value means equality
+    let operators = match condition.cast::<PyDict>() {
+        Ok(dict) if dict.keys().iter().all(|k| k.extract::<String>().is_ok_and(|k| k.starts_with('$'))) => dict,
+        _ => return cmp("$eq", FilterOp::Eq, condition),
+    };
+
+    let mut exprs = Vec::with_capacity(operators.len());
+    for (op, value) in operators.iter() {
+        let name: String = op.extract()?;
+        exprs.push(cmp(&name, comparison_op(&name)?, &value)?);
+    }
+    Ok(match exprs.len() {
+        1 => exprs.remove(0),
+        _ => FilterExpr::And(exprs),
+    })
+}
+
+/// Parse a filter dict into a vecstore filter expression
+///
+/// The top-level entries are combined with AND; an empty dict matches
+/// every record.
+pub(crate) fn parse_filter(filter: &Bound<'_, PyDict>) -> PyResult<FilterExpr> {
+    let mut exprs = Vec::with_capacity(filter.len());
+    for (key, value) in filter.iter() {
+        let key: String = key
+            .extract()
+            .map_err(|_| invalid("keys must be strings".to_string()))?;
+        exprs.push(match key.as_str() {
+            "$and" => FilterExpr::And(parse_list(&key, &value)?),
+            "$or" => FilterExpr::Or(parse_list(&key, &value)?),
+            "$not" => {
+                let dict = value
+                    .cast::<PyDict>()
+                    .map_err(|_| invalid("'$not' expects a filter dict".to_string()))?;
+                FilterExpr::Not(Box::new(parse_filter(dict)?))
+            }
+            op if op.starts_with('$') => {
+                return Err(invalid(format!("unknown logical operator '{}'", op)));
+            }
+            field => parse_field(field, &value)?,
+        });
+    }
+    Ok(match exprs.len() {
+        1 => exprs.remove(0),
+        _ => FilterExpr::And(exprs),
+    })
+}
+
+/// Check whether a record's metadata satisfies a parsed filter
+pub(crate) fn matches(filter: &FilterExpr, metadata: &Metadata) -> bool {
+    evaluate_filter(filter, metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(fields: Value) -> Metadata {
+        let Value::Object(map) = fields else { unreachable!() };
+        Metadata {
+            fields: map.into_iter().collect(),
+        }
+    }
+
+    /// Parse a filter written as a Python literal
+    fn parse(source: &str) -> PyResult<FilterExpr> {
+        Python::initialize();
+        Python::attach(|py| {
+            let code = std::ffi::CString::new(source).unwrap();
+            let value = py.eval(&code, None, None)?;
+            parse_filter(value.cast::<PyDict>()?)
+        })
+    }
+
+    #[test]
+    fn equality_and_operators() {
+        let doc = metadata(json!({"author": "alice", "year": 2022, "tags": ["rust", "py"]}));
+        assert!(matches(&parse(r#"{"author": "alice"}"#).unwrap(), &doc));
+        assert!(!matches(&parse(r#"{"author": "bob"}"#).unwrap(), &doc));
+        assert!(matches(&parse(r#"{"year": {"$gte": 2020, "$lt": 2024}}"#).unwrap(), &doc));
+        assert!(!matches(&parse(r#"{"year": {"$gt": 2022}}"#).unwrap(), &doc));
+        assert!(matches(&parse(r#"{"author": {"$in": ["alice", "carol"]}}"#).unwrap(), &doc));
+        assert!(matches(&parse(r#"{"author": {"$startswith": "al"}}"#).unwrap(), &doc));
+    }
+
+    #[test]
+    fn logical_operators() {
+        let doc = metadata(json!({"lang": "de", "year": 2019}));
+        assert!(matches(&parse(r#"{"$or": [{"lang": "en"}, {"lang": "de"}]}"#).unwrap(), &doc));
+        assert!(!matches(&parse(r#"{"$and": [{"lang": "de"}, {"year": 2020}]}"#).unwrap(), &doc));
+        assert!(matches(&parse(r#"{"$not": {"lang": "en"}}"#).unwrap(), &doc));
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = parse("{}").unwrap();
+        assert!(matches(&filter, &metadata(json!({}))));
+    }
+
+    #[test]
+    fn missing_field_never_matches_a_comparison() {
+        let filter = parse(r#"{"year": {"$lt": 3000}}"#).unwrap();
+        assert!(!matches(&filter, &metadata(json!({"title": "t"}))));
+    }
+
+    #[test]
+    fn malformed_filters_are_rejected() {
+        for source in [
+            r#"{"year": {"$between": [1, 2]}}"#,
+            r#"{"$xor": []}"#,
+            r#"{"year": {"$in": 5}}"#,
+            r#"{"year": {"$gt": [1]}}"#,
+            r#"{"title": {"$startswith": 1}}"#,
+            r#"{"$or": {"a": 1}}"#,
+            r#"{1: "a"}"#,
+        ] {
+            assert!(parse(source).is_err(), "{} should be rejected", source);
+        }
+    }
+}
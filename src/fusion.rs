@@ -0,0 +1,276 @@
+//! Rank fusion over result lists that were already fetched, and score
+//! fusion across stores
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::HashMap;
+use vecstore::Metadata;
+
+use crate::{VectorStore, OVERFETCH_FACTOR};
+
+/// RRF constant from the original paper (Cormack et al., 2009)
+const DEFAULT_RRF_CONSTANT: f64 = 60.0;
+
+/// Merge several ranked result lists with Reciprocal Rank Fusion
+///
+/// Every result contributes `1 / (constant + rank)` to its id, where rank
+/// starts at 1 within its list; an id missing from a list adds nothing for
+/// it. Raw scores are ignored, so lists from different metrics or query
+/// rewrites can be combined directly.
+///
+/// Args:
+///     result_lists: Lists of result dicts as returned by `search`; each
+///         dict needs an "id" key
+///     k: Number of fused results to return (default: 5)
+///     constant: RRF constant, larger values flatten the rank weights
+///         (default: 60)
+///
+/// Returns:
+///     List of result dicts sorted by fused score (descending). Each is
+///     a copy of the id's first occurrence with "score" set to the fused score.
+#[pyfunction]
+#[pyo3(signature = (result_lists, k = 5, constant = DEFAULT_RRF_CONSTANT))]
+pub(crate) fn fuse_results<'py>(
+    py: Python<'py>,
+    result_lists: Vec<Vec<Bound<'py, PyDict>>>,
+    k: usize,
+    constant: f64,
+) -> PyResult<Bound<'py, PyList>> {
+    if !(constant >= 0.0 && constant.is_finite()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "constant must be a finite, non-negative number, got {}",
+            constant
+        )));
+    }
+
+    // Python dict so ids of any hashable type are matched like Python would
+    let positions = PyDict::new(py);
+    let mut fused: Vec<(f64, Bound<'py, PyDict>)> = Vec::new();
+
+    for results in &result_lists {
+        for (rank, result) in results.iter().enumerate() {
+            let id = result.get_item("id")?.ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>("Result is missing 'id'")
+            })?;
+            let contribution = 1.0 / (constant + rank as f64 + 1.0);
+
+            match positions.get_item(&id)? {
+                Some(index) => fused[index.extract::<usize>()?].0 += contribution,
+                None => {
+                    positions.set_item(&id, fused.len())?;
+                    fused.push((contribution, result.copy()?));
+                }
+            }
+        }
+    }
+
+    // Stable sort keeps first-seen order for equal fused scores
+    fused.sort_by(|a, b| b.0.total_cmp(&a.0));
+    fused.truncate(k);
+
+    let output = PyList::empty(py);
+    for (score, result) in fused {
+        result.set_item("score", score)?;
+        output.append(result)?;
+    }
+    Ok(output)
+}
+
+/// Query several stores and combine their min-max normalized scores per id
+///
+/// Each store is queried with its own vector (e.g. a text embedding for the
+/// body store and a title embedding for the title store). Scores are
+/// min-max normalized within each store's candidates so different metrics
+/// and embedding models are comparable, then summed per id with the store's
+/// weight; an id a store did not return contributes 0 for it. The stores
+/// must index the same documents under the same ids, so they must share an
+/// id_type; ids that exist in only one store are still ranked, on that
+/// store's contribution alone.
+///
+/// Args:
+///     stores: VectorStores to query
+///     vectors: One query vector per store, matching its dimension
+///     weights: One weight per store
+///     k: Number of fused results to return (default: 5)
+///
+/// Returns:
+///     List of result dicts (id, score, title, url, summary) sorted by
+///     combined score (descending). Metadata comes from the first store
+///     that returned the id.
+///
+/// Raises:
+///     ValueError: If the argument lengths differ, a weight is not finite,
+///         the stores' id types differ, or a vector has the wrong dimension
+#[pyfunction]
+#[pyo3(signature = (stores, vectors, weights, k = 5))]
+pub(crate) fn search_ensemble<'py>(
+    py: Python<'py>,
+    stores: Vec<PyRef<'py, VectorStore>>,
+    vectors: Vec<Vec<f32>>,
+    weights: Vec<f32>,
+    k: usize,
+) -> PyResult<Bound<'py, PyList>> {
+    if stores.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "stores must not be empty",
+        ));
+    }
+    if vectors.len() != stores.len() || weights.len() != stores.len() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Got {} stores, {} vectors and {} weights",
+            stores.len(),
+            vectors.len(),
+            weights.len()
+        )));
+    }
+    if let Some(weight) = weights.iter().find(|w| !w.is_finite()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "weights must be finite, got {}",
+            weight
+        )));
+    }
+    if stores.iter().any(|s| s.id_type != stores[0].id_type) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "All stores must use the same id_type to share an id space",
+        ));
+    }
+    if k == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1"));
+    }
+    for (store, vector) in stores.iter().zip(&vectors) {
+        store.check_dimension(vector)?;
+    }
+
+    let fetch = k.saturating_mul(OVERFETCH_FACTOR);
+    // Per id: combined score and the index of the first store that returned it
+    let mut combined: HashMap<String, (f32, usize, Metadata)> = HashMap::new();
+    for (index, ((store, vector), weight)) in stores.iter().zip(vectors).zip(weights).enumerate() {
+        let results = store.run_query(vector, fetch)?;
+        let (min, max) = results
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), r| (lo.min(r.score), hi.max(r.score)));
+        let range = max - min;
+
+        for result in results {
+            // A single candidate, or all equal scores, count as a full match
+            let normalized = if range > 0.0 { (result.score - min) / range } else { 1.0 };
+            combined
+                .entry(result.id)
+                .or_insert_with(|| (0.0, index, result.metadata))
+                .0 += weight * normalized;
+        }
+    }
+
+    let mut ranked: Vec<(String, (f32, usize, Metadata))> = combined.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.0.total_cmp(&a.1.0).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(k);
+
+    let output = PyList::empty(py);
+    for (key, (score, index, metadata)) in ranked {
+        output.append(stores[index].result_dict(py, &key, score, &metadata)?)?;
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDictMethods;
+    use serde_json::json;
+
+    fn result<'py>(py: Python<'py>, id: &str, score: f64) -> Bound<'py, PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("id", id).unwrap();
+        dict.set_item("score", score).unwrap();
+        dict
+    }
+
+    fn ids(results: &Bound<'_, PyList>) -> Vec<String> {
+        results
+            .iter()
+            .map(|r| r.get_item("id").unwrap().extract().unwrap())
+            .collect()
+    }
+
+    fn scores(results: &Bound<'_, PyList>) -> Vec<f64> {
+        results
+            .iter()
+            .map(|r| r.get_item("score").unwrap().extract().unwrap())
+            .collect()
+    }
+
+    /// In-memory store holding `(id, vector)` pairs
+    fn store(py: Python<'_>, records: &[(&str, [f32; 2])]) -> Py<VectorStore> {
+        let options = crate::StoreOptions::default();
+        let store = VectorStore::from_dir(crate::create_temp_dir().unwrap(), Some(2), true, options).unwrap();
+        {
+            let mut inner = store.write_store().unwrap();
+            for (id, vector) in records {
+                let metadata = Metadata {
+                    fields: HashMap::from([("title".to_string(), json!(id))]),
+                };
+                inner.upsert(id.to_string(), vector.to_vec(), metadata).unwrap();
+            }
+        }
+        Py::new(py, store).unwrap()
+    }
+
+    #[test]
+    fn rrf_rewards_agreement_across_lists() {
+        Python::initialize();
+        Python::attach(|py| {
+            let first = vec![result(py, "a", 0.9), result(py, "b", 0.8), result(py, "c", 0.1)];
+            let second = vec![result(py, "b", 0.7), result(py, "c", 0.6)];
+            let fused = fuse_results(py, vec![first, second], 5, 60.0).unwrap();
+
+            assert_eq!(ids(&fused), ["b", "c", "a"]);
+            let expected_b = 1.0 / 62.0 + 1.0 / 61.0;
+            assert!((scores(&fused)[0] - expected_b).abs() < 1e-12);
+        });
+    }
+
+    #[test]
+    fn rrf_truncates_and_keeps_first_seen_order_on_ties() {
+        Python::initialize();
+        Python::attach(|py| {
+            let first = vec![result(py, "a", 1.0)];
+            let second = vec![result(py, "b", 1.0)];
+            let fused = fuse_results(py, vec![first, second], 1, 60.0).unwrap();
+            assert_eq!(ids(&fused), ["a"]);
+            assert!(fuse_results(py, vec![], 5, -1.0).is_err());
+        });
+    }
+
+    #[test]
+    fn ensemble_combines_normalized_scores_by_weight() {
+        Python::initialize();
+        Python::attach(|py| {
+            let body = store(py, &[("a", [1.0, 0.0]), ("b", [0.0, 1.0])]);
+            let title = store(py, &[("a", [0.0, 1.0]), ("b", [1.0, 0.0])]);
+            let query = vec![vec![1.0, 0.0], vec![1.0, 0.0]];
+
+            // Each store prefers a different document; the weight decides
+            let stores = || vec![body.borrow(py), title.borrow(py)];
+            let fused = search_ensemble(py, stores(), query.clone(), vec![1.0, 0.5], 2).unwrap();
+            assert_eq!(ids(&fused), ["a", "b"]);
+            assert_eq!(scores(&fused), [1.0, 0.5]);
+
+            let fused = search_ensemble(py, stores(), query, vec![0.5, 1.0], 2).unwrap();
+            assert_eq!(ids(&fused), ["b", "a"]);
+        });
+    }
+
+    #[test]
+    fn ensemble_validates_arguments() {
+        Python::initialize();
+        Python::attach(|py| {
+            let a = store(py, &[("a", [1.0, 0.0])]);
+            let one = || vec![a.borrow(py)];
+            assert!(search_ensemble(py, vec![], vec![], vec![], 5).is_err());
+            assert!(search_ensemble(py, one(), vec![vec![1.0, 0.0]], vec![], 5).is_err());
+            assert!(search_ensemble(py, one(), vec![vec![1.0, 0.0]], vec![f32::NAN], 5).is_err());
+            assert!(search_ensemble(py, one(), vec![vec![1.0, 0.0]], vec![1.0], 0).is_err());
+            assert!(search_ensemble(py, one(), vec![vec![1.0, 0.0, 0.0]], vec![1.0], 5).is_err());
+        });
+    }
+}
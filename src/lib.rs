@@ -1,275 +1,5053 @@
+mod embedding;
+mod filter;
+mod fusion;
+mod logging;
+mod mmap_store;
+mod npz;
+mod pca;
+mod result;
+mod scoring;
+mod sparse;
+mod schema;
+mod vector_ops;
+mod wal;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{debug, error, info, warn};
+use numpy::{PyArray1, PyArrayMethods, PyReadwriteArray2, PyUntypedArrayMethods};
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList};
+use pyo3::types::{PyDict, PyList, PySet};
+use rayon::prelude::*;
 use serde_json::json;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
-use vecstore::{Metadata, Query, VecStore};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::time::{Duration, Instant};
+use vecstore::{Distance, Metadata, Neighbor, Query, Record, VecStore};
+
+/// How document ids are exchanged with Python
+///
+/// VecStore only supports string ids, so integer ids are stored as their
+/// decimal representation and converted back when returned to Python.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum IdType {
+    Str,
+    Int,
+}
+
+impl IdType {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "str" => Ok(IdType::Str),
+            "int" => Ok(IdType::Int),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid id_type '{}'. Expected 'str' or 'int'",
+                other
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            IdType::Str => "str",
+            IdType::Int => "int",
+        }
+    }
+
+    /// Convert a Python id into the string key used by VecStore
+    fn key_from_py(self, id: &Bound<'_, PyAny>) -> PyResult<String> {
+        match self {
+            IdType::Str => id.extract::<String>().map_err(|_| {
+                PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                    "Expected str id, got {}",
+                    id.get_type().name().map(|n| n.to_string()).unwrap_or_default()
+                ))
+            }),
+            // bool subclasses int, so True would otherwise be stored as id 1
+            IdType::Int if id.is_instance_of::<pyo3::types::PyBool>() => {
+                Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("Expected int id, got bool"))
+            }
+            IdType::Int => id.extract::<i64>().map(|n| n.to_string()).map_err(|_| {
+                let message = if id.is_instance_of::<pyo3::types::PyInt>() {
+                    format!("Int id {} is outside the 64-bit signed range", id)
+                } else {
+                    format!(
+                        "Expected int id, got {}",
+                        id.get_type().name().map(|n| n.to_string()).unwrap_or_default()
+                    )
+                };
+                PyErr::new::<pyo3::exceptions::PyTypeError, _>(message)
+            }),
+        }
+    }
+
+    /// Convert a VecStore key back into the Python id type
+    fn key_to_py(self, py: Python, key: &str) -> PyResult<Py<PyAny>> {
+        match self {
+            IdType::Int => match key.parse::<i64>() {
+                Ok(n) => Ok(n.into_pyobject(py)?.into_any().unbind()),
+                Err(_) => Ok(key.into_pyobject(py)?.into_any().unbind()),
+            },
+            IdType::Str => Ok(key.into_pyobject(py)?.into_any().unbind()),
+        }
+    }
+}
+
+/// Whether document content is kept after vectorization
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ContentMode {
+    /// Content is discarded (the default, memory-efficient design)
+    Discard,
+    /// Content is gzip-compressed and kept in metadata under `_content`
+    Compressed,
+}
+
+impl ContentMode {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "none" => Ok(ContentMode::Discard),
+            "compressed" => Ok(ContentMode::Compressed),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid store_content '{}'. Expected 'none' or 'compressed'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Precision a record's vector is kept at
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VectorDtype {
+    F32,
+    /// Rounded to half precision. VecStore only holds f32 vectors, so this
+    /// changes the values (and scores) but not the memory used.
+    F16,
+    /// Rounded to bfloat16: the f32 exponent range with an 8-bit mantissa,
+    /// so coarser than f16 (~2-3 significant digits vs ~3-4) but without
+    /// f16's overflow above 65504. Like f16, stored and scored as f32.
+    BF16,
+}
+
+impl VectorDtype {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "f32" => Ok(VectorDtype::F32),
+            "f16" => Ok(VectorDtype::F16),
+            "bf16" => Ok(VectorDtype::BF16),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid dtype '{}'. Expected 'f32', 'f16' or 'bf16'",
+                other
+            ))),
+        }
+    }
+
+    /// Dtype recorded in a record's metadata, f32 when unmarked
+    fn of(metadata: &Metadata) -> Self {
+        match metadata.fields.get(DTYPE_FIELD).and_then(|v| v.as_str()) {
+            Some("f16") => VectorDtype::F16,
+            Some("bf16") => VectorDtype::BF16,
+            _ => VectorDtype::F32,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            VectorDtype::F32 => "f32",
+            VectorDtype::F16 => "f16",
+            VectorDtype::BF16 => "bf16",
+        }
+    }
+
+    /// Round a vector to this precision; values are upconverted back to f32
+    fn quantize(self, vector: Vec<f32>) -> Vec<f32> {
+        match self {
+            VectorDtype::F32 => vector,
+            VectorDtype::F16 => vector
+                .into_iter()
+                .map(|x| half::f16::from_f32(x).to_f32())
+                .collect(),
+            VectorDtype::BF16 => vector
+                .into_iter()
+                .map(|x| half::bf16::from_f32(x).to_f32())
+                .collect(),
+        }
+    }
+
+    /// Record the dtype in metadata; f32 is the default and left unmarked
+    fn mark(self, metadata: &mut Metadata) {
+        match self {
+            VectorDtype::F32 => metadata.fields.remove(DTYPE_FIELD),
+            other => metadata.fields.insert(DTYPE_FIELD.to_string(), json!(other.name())),
+        };
+    }
+}
+
+/// Metadata key marking records stored at reduced precision
+const DTYPE_FIELD: &str = "_dtype";
+
+/// Representation of the scores returned by `search`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScoreKind {
+    /// Higher = more similar (the default)
+    Similarity,
+    /// Smaller = more similar, see `scoring::to_distance`
+    Distance,
+}
+
+impl ScoreKind {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "similarity" => Ok(ScoreKind::Similarity),
+            "distance" => Ok(ScoreKind::Distance),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid score_as '{}'. Expected 'similarity' or 'distance'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Normalization applied to the scores of the returned page, see `search(score_transform=...)`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScoreTransform {
+    None,
+    /// `exp(s / temperature)`, normalized to sum to 1
+    Softmax,
+    /// Rescaled so the best score is 1.0 and the worst 0.0
+    MinMax,
+}
+
+impl ScoreTransform {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "none" => Ok(ScoreTransform::None),
+            "softmax" => Ok(ScoreTransform::Softmax),
+            "minmax" => Ok(ScoreTransform::MinMax),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid score_transform '{}'. Expected 'none', 'softmax' or 'minmax'",
+                other
+            ))),
+        }
+    }
+
+    fn apply(self, scores: &mut [f32], temperature: f32) {
+        let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        match self {
+            ScoreTransform::None => {}
+            ScoreTransform::Softmax => {
+                // Shift by the max so exp never overflows
+                let mut total = 0.0f64;
+                for s in scores.iter_mut() {
+                    *s = ((*s - max) / temperature).exp();
+                    total += *s as f64;
+                }
+                scores.iter_mut().for_each(|s| *s = (*s as f64 / total) as f32);
+            }
+            ScoreTransform::MinMax => {
+                let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+                let range = max - min;
+                for s in scores.iter_mut() {
+                    *s = if range > 0.0 { (*s - min) / range } else { 1.0 };
+                }
+            }
+        }
+    }
+}
+
+/// Per-store settings chosen at construction time
+struct StoreOptions {
+    id_type: IdType,
+    readonly: bool,
+    content_mode: ContentMode,
+    compression_level: u32,
+    num_threads: Option<usize>,
+    check_dimensions: bool,
+    normalize: bool,
+    retries: usize,
+    retry_backoff_ms: u64,
+    id_normalizer: Option<Py<PyAny>>,
+    storage_dtype: VectorDtype,
+    schema: Option<schema::Schema>,
+}
+
+impl Default for StoreOptions {
+    fn default() -> Self {
+        StoreOptions {
+            id_type: IdType::Str,
+            readonly: false,
+            content_mode: ContentMode::Discard,
+            compression_level: 6,
+            num_threads: None,
+            check_dimensions: true,
+            normalize: false,
+            retries: 0,
+            retry_backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+            id_normalizer: None,
+            storage_dtype: VectorDtype::F32,
+            schema: None,
+        }
+    }
+}
+
+/// Metadata key holding compressed content when `store_content="compressed"`
+const CONTENT_FIELD: &str = "_content";
+
+/// Metadata key holding the insertion sequence number, see `VectorStore.search(stable_insertion_order=...)`
+const SEQ_FIELD: &str = "_seq";
+
+/// Metadata key holding the time of the last write in unix milliseconds, see `VectorStore.modified_since`
+const UPDATED_FIELD: &str = "_updated_at";
+
+/// Metadata fields starting with this prefix are internal bookkeeping and
+/// are never returned to Python as regular metadata
+const INTERNAL_PREFIX: char = '_';
+
+/// How many extra candidates re-ranking searches fetch per requested result
+const OVERFETCH_FACTOR: usize = 4;
+
+/// Queries slower than this are logged at `warn`
+const SLOW_QUERY: Duration = Duration::from_millis(100);
+
+/// Lock waits longer than this are logged at `warn`, shorter ones at `debug`
+const SLOW_LOCK_WAIT: Duration = Duration::from_millis(50);
+
+/// Element count vecstore's HNSW backend pre-sizes its graph for
+const BACKEND_PRESIZED_ELEMENTS: usize = 100_000;
+
+/// Delay before the first retry of a transient I/O error; doubles per attempt
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 50;
+
+/// Whether a VecStore error is worth retrying
+///
+/// Only I/O failures that may clear up on their own count as transient;
+/// validation errors (dimension mismatch, bad ids) carry no `io::Error` and
+/// missing files or permissions will not fix themselves.
+fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    use std::io::ErrorKind;
+
+    let mut source = Some(error);
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            return !matches!(
+                io.kind(),
+                ErrorKind::NotFound
+                    | ErrorKind::PermissionDenied
+                    | ErrorKind::InvalidInput
+                    | ErrorKind::InvalidData
+                    | ErrorKind::Unsupported
+            );
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// What `set_batch` does when the callback returns a wrong-length vector
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BadVectorPolicy {
+    Error,
+    Skip,
+}
+
+impl BadVectorPolicy {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "error" => Ok(BadVectorPolicy::Error),
+            "skip" => Ok(BadVectorPolicy::Skip),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid on_bad_vector '{}'. Expected 'error' or 'skip'",
+                other
+            ))),
+        }
+    }
+}
+
+/// What `set_vectors_batch` does when an id appears more than once in one batch
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DuplicatePolicy {
+    LastWins,
+    FirstWins,
+    Error,
+}
+
+impl DuplicatePolicy {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "last_wins" => Ok(DuplicatePolicy::LastWins),
+            "first_wins" => Ok(DuplicatePolicy::FirstWins),
+            "error" => Ok(DuplicatePolicy::Error),
+            other => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid on_duplicate '{}'. Expected 'last_wins', 'first_wins' or 'error'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Create a fresh temporary directory to back a store
+fn create_temp_dir() -> PyResult<PathBuf> {
+    let temp_dir = std::env::temp_dir().join(format!("tf_vecstore_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&temp_dir).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to create temp directory: {}",
+            e
+        ))
+    })?;
+    Ok(temp_dir)
+}
+
+/// Write the contents of `root` into a tar archive, gzip-compressed for .gz/.tgz paths
+fn write_archive(tar_path: &Path, root: &Path) -> std::io::Result<()> {
+    let file = File::create(tar_path)?;
+    let gzip = tar_path
+        .extension()
+        .is_some_and(|ext| ext == "gz" || ext == "tgz");
+
+    if gzip {
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        builder.append_dir_all(".", root)?;
+        builder.into_inner()?.finish()?;
+    } else {
+        let mut builder = tar::Builder::new(file);
+        builder.append_dir_all(".", root)?;
+        builder.into_inner()?;
+    }
+    Ok(())
+}
+
+/// Unpack a (optionally gzip-compressed) tar archive into `dest`
+fn unpack_archive(tar_path: &Path, dest: &Path) -> std::io::Result<()> {
+    let mut file = File::open(tar_path)?;
+    let mut magic = [0u8; 2];
+    let gzip = file.read(&mut magic)? == 2 && magic == [0x1f, 0x8b];
+    file.seek(SeekFrom::Start(0))?;
+
+    if gzip {
+        tar::Archive::new(GzDecoder::new(file)).unpack(dest)
+    } else {
+        tar::Archive::new(file).unpack(dest)
+    }
+}
+
+/// Total size in bytes of the regular files under `path`; symlinks are not followed
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Validate that parallel batch lists have equal lengths
+///
+/// `None` entries are optional lists that were not provided. The error names
+/// the first list whose length differs from the first one and by how much.
+fn check_batch_lengths(lists: &[(&str, Option<usize>)]) -> PyResult<()> {
+    let mut provided = lists.iter().filter_map(|(name, len)| len.map(|len| (*name, len)));
+    let Some((first_name, expected)) = provided.next() else {
+        return Ok(());
+    };
+
+    for (name, len) in provided {
+        if len != expected {
+            let (relation, diff) = if len < expected {
+                ("short", expected - len)
+            } else {
+                ("long", len - expected)
+            };
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Batch length mismatch: {} has {} items but {} has {} ({} {})",
+                name, len, first_name, expected, diff, relation
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Mark `metadata` as written now, see `UPDATED_FIELD`
+///
+/// Every path that writes a record's vector or metadata calls this, since
+/// VecStore's own `created_at` only moves on upsert and has one-second
+/// resolution.
+fn stamp_updated(metadata: &mut Metadata) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    metadata.fields.insert(UPDATED_FIELD.to_string(), json!(now));
+}
+
+/// Build the stored metadata for a document: title, url and summary
+///
+/// Every insert path goes through here, so a missing summary is always
+/// stored as an empty string and all records have the same fields. `seq`
+/// is the insertion sequence number from `VectorStore::next_seq`.
+fn document_metadata(title: &str, url: &str, summary: Option<&str>, seq: u64) -> Metadata {
+    let mut metadata = Metadata {
+        fields: HashMap::new(),
+    };
+    metadata.fields.insert(SEQ_FIELD.to_string(), json!(seq));
+    stamp_updated(&mut metadata);
+    metadata.fields.insert("title".to_string(), json!(title));
+    metadata.fields.insert("url".to_string(), json!(url));
+    metadata.fields.insert("summary".to_string(), json!(summary.unwrap_or("")));
+    metadata
+}
+
+/// Convert a JSON metadata value into the equivalent Python object
+fn json_to_py(py: Python, value: &serde_json::Value) -> PyResult<Py<PyAny>> {
+    use serde_json::Value;
+
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.into_pyobject(py)?.to_owned().into_any().unbind(),
+        // Integers stay Python ints (exact, beyond 2^53); only JSON floats
+        // become Python floats
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_pyobject(py)?.into_any().unbind(),
+            (None, Some(u)) => u.into_pyobject(py)?.into_any().unbind(),
+            (None, None) => n.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any().unbind(),
+        },
+        Value::String(s) => s.into_pyobject(py)?.into_any().unbind(),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(json_to_py(py, item)?)?;
+            }
+            list.into_any().unbind()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, item) in map {
+                dict.set_item(key, json_to_py(py, item)?)?;
+            }
+            dict.into_any().unbind()
+        }
+    })
+}
+
+/// Convert a Python metadata value into JSON
+///
+/// Supports None, bool, int, float, str and (nested) lists and dicts with
+/// string keys. Ints are kept as JSON integers, so any value in the i64 or
+/// u64 range round-trips exactly through `json_to_py`.
+fn py_to_json(value: &Bound<'_, PyAny>) -> PyResult<serde_json::Value> {
+    use pyo3::types::{PyBool, PyFloat, PyInt, PyString};
+    use serde_json::Value;
+
+    // bool before int: Python bools are ints too
+    if value.is_none() {
+        Ok(Value::Null)
+    } else if value.is_instance_of::<PyBool>() {
+        Ok(Value::Bool(value.extract()?))
+    } else if value.is_instance_of::<PyInt>() {
+        match value.extract::<i64>() {
+            Ok(i) => Ok(json!(i)),
+            Err(_) => value.extract::<u64>().map(|u| json!(u)).map_err(|_| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Integer metadata value {} is outside the 64-bit range",
+                    value
+                ))
+            }),
+        }
+    } else if value.is_instance_of::<PyFloat>() {
+        Ok(json!(value.extract::<f64>()?))
+    } else if value.is_instance_of::<PyString>() {
+        Ok(Value::String(value.extract()?))
+    } else if let Ok(list) = value.cast::<PyList>() {
+        list.iter().map(|item| py_to_json(&item)).collect::<PyResult<Vec<_>>>().map(Value::Array)
+    } else if let Ok(dict) = value.cast::<PyDict>() {
+        let mut map = serde_json::Map::new();
+        for (key, item) in dict.iter() {
+            map.insert(key.extract::<String>()?, py_to_json(&item)?);
+        }
+        Ok(Value::Object(map))
+    } else {
+        Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+            "Unsupported metadata value type: {}",
+            value.get_type().name()?
+        )))
+    }
+}
+
+/// Round a score to a fixed number of decimal places
+fn round_to(score: f64, places: u32) -> f64 {
+    let factor = 10f64.powi(places.min(15) as i32);
+    (score * factor).round() / factor
+}
+
+/// Log how long a lock acquisition had to wait behind another holder
+fn log_lock_wait(kind: &str, waited: Duration) {
+    let ms = waited.as_secs_f64() * 1000.0;
+    if waited >= SLOW_LOCK_WAIT {
+        warn!("Waited {:.1} ms for the {} lock", ms, kind);
+    } else {
+        debug!("Waited {:.3} ms for the {} lock", ms, kind);
+    }
+}
+
+/// Acquisition counters for one side of the store lock, see `VectorStore.stats`
+#[derive(Default)]
+struct LockStats {
+    acquisitions: AtomicU64,
+    /// Acquisitions that found the lock held and had to wait
+    contended: AtomicU64,
+    total_wait_ns: AtomicU64,
+    max_wait_ns: AtomicU64,
+}
+
+impl LockStats {
+    fn record(&self, waited: Option<Duration>) {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        if let Some(waited) = waited {
+            let ns = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+            self.contended.fetch_add(1, Ordering::Relaxed);
+            self.total_wait_ns.fetch_add(ns, Ordering::Relaxed);
+            self.max_wait_ns.fetch_max(ns, Ordering::Relaxed);
+        }
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let ms = |ns: &AtomicU64| ns.load(Ordering::Relaxed) as f64 / 1e6;
+        let dict = PyDict::new(py);
+        dict.set_item("acquisitions", self.acquisitions.load(Ordering::Relaxed))?;
+        dict.set_item("contended", self.contended.load(Ordering::Relaxed))?;
+        dict.set_item("total_wait_ms", ms(&self.total_wait_ns))?;
+        dict.set_item("max_wait_ms", ms(&self.max_wait_ns))?;
+        Ok(dict)
+    }
+}
+
+/// Build a rayon pool with a fixed number of worker threads
+fn build_pool(num_threads: usize) -> PyResult<rayon::ThreadPool> {
+    if num_threads == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "num_threads must be at least 1",
+        ));
+    }
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to create thread pool: {}",
+                e
+            ))
+        })
+}
+
+/// Gzip-compress content and encode it for storage in JSON metadata
+fn compress_content(content: &str, level: u32) -> PyResult<String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+    encoder
+        .write_all(content.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|bytes| BASE64.encode(bytes))
+        .map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to compress content: {}",
+                e
+            ))
+        })
+}
+
+/// Decode content stored by `compress_content`, if the record has any
+fn decompress_content(metadata: &Metadata) -> PyResult<Option<String>> {
+    let Some(encoded) = metadata.fields.get(CONTENT_FIELD).and_then(|v| v.as_str()) else {
+        return Ok(None);
+    };
+
+    let corrupted = |e: String| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+            "Failed to decompress content: {}",
+            e
+        ))
+    };
+    let bytes = BASE64.decode(encoded).map_err(|e| corrupted(e.to_string()))?;
+    let mut content = String::new();
+    GzDecoder::new(bytes.as_slice())
+        .read_to_string(&mut content)
+        .map_err(|e| corrupted(e.to_string()))?;
+
+    Ok(Some(content))
+}
+
+/// Metadata key holding a record's query-time boost factor
+const BOOST_FIELD: &str = "boost";
+
+/// Boost factor of a record, 1.0 when none is stored
+fn record_boost(metadata: &Metadata) -> f32 {
+    metadata
+        .fields
+        .get(BOOST_FIELD)
+        .and_then(|v| v.as_f64())
+        .map_or(1.0, |b| b as f32)
+}
+
+/// Default metadata key holding a precomputed rerank score, see `search(use_rerank_field=...)`
+const DEFAULT_RERANK_FIELD: &str = "rerank_score";
+
+/// Reject boost factors that would break score ordering
+fn check_boost(boost: f64) -> PyResult<()> {
+    if !boost.is_finite() || boost < 0.0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "boost must be a finite, non-negative number, got {}",
+            boost
+        )));
+    }
+    Ok(())
+}
+
+/// Order two JSON metadata values: numbers numerically, strings and bools
+/// by value; values of different types compare equal
+fn compare_json(a: &serde_json::Value, b: &serde_json::Value) -> std::cmp::Ordering {
+    use serde_json::Value;
+    use std::cmp::Ordering;
+
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .zip(y.as_f64())
+            .map_or(Ordering::Equal, |(x, y)| x.total_cmp(&y)),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Position of a JSON value's type when values of mixed types are sorted
+fn json_type_rank(value: &serde_json::Value) -> u8 {
+    use serde_json::Value;
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+/// Read a string metadata field, defaulting to "" when missing or not a string
+fn meta_str<'a>(metadata: &'a Metadata, field: &str) -> &'a str {
+    metadata.fields.get(field).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// Build a search result dict: id, score, title, url and summary
+fn result_dict<'py>(
+    py: Python<'py>,
+    id_type: IdType,
+    key: &str,
+    score: f32,
+    metadata: &Metadata,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("id", id_type.key_to_py(py, key)?)?;
+    dict.set_item("score", score)?;
+
+    // Extract metadata fields (title, url, summary - no content, no vector)
+    dict.set_item("title", meta_str(metadata, "title"))?;
+    dict.set_item("url", meta_str(metadata, "url"))?;
+    dict.set_item("summary", meta_str(metadata, "summary"))?;
+
+    Ok(dict)
+}
+
+/// Document fields every result dict is expected to carry
+const DOCUMENT_FIELDS: [&str; 3] = ["title", "url", "summary"];
+
+/// Set each document field the record lacks (or holds as a non-string) to None
+fn set_missing_to_none(dict: &Bound<'_, PyDict>, metadata: &Metadata) -> PyResult<()> {
+    for field in DOCUMENT_FIELDS {
+        if metadata.fields.get(field).and_then(|v| v.as_str()).is_none() {
+            dict.set_item(field, dict.py().None())?;
+        }
+    }
+    Ok(())
+}
 
 /// Vector store that manages embeddings and metadata using VecStore
 /// 
 /// This implementation is optimized for memory efficiency and performance:
 /// - Only stores vectors and metadata (title, url, summary)
-/// - Does NOT store content text - it's discarded after vectorization
+/// - Does NOT store content text by default - it's discarded after vectorization
+///   (opt in with `store_content="compressed"` to keep a gzip-compressed copy)
 /// - Uses Python callback to convert content to vectors on-the-fly
 /// - Thread-safe with RwLock for concurrent read access
 /// - No unsafe blocks - all operations are memory-safe
 #[pyclass]
 struct VectorStore {
-    store: Arc<RwLock<VecStore>>,
+    /// None once `close()` has run
+    store: Option<Arc<RwLock<VecStore>>>,
     dimension: usize,
+    root: PathBuf,
     temp_path: Option<PathBuf>,
+    id_type: IdType,
+    readonly: bool,
+    content_mode: ContentMode,
+    compression_level: u32,
+    /// Dedicated pool for the brute-force paths; None uses rayon's global pool
+    pool: Option<Arc<rayon::ThreadPool>>,
+    /// When false, `set_vector` and the search paths skip the length check
+    check_dimensions: bool,
+    /// Unit-normalize incoming vectors, see `VectorStore(normalize=True)`
+    normalize: bool,
+    /// Extra attempts for VecStore disk operations failing with transient I/O errors
+    retries: usize,
+    retry_backoff: Duration,
+    /// Applied to every id passed in from Python, see `VectorStore(id_normalizer=...)`
+    id_normalizer: Option<Py<PyAny>>,
+    /// Precision of inserted vectors unless `set_vector(dtype=...)` overrides it
+    storage_dtype: VectorDtype,
+    /// Field types checked on every write, see `VectorStore(schema=...)`
+    schema: Option<schema::Schema>,
+    metric: Distance,
+    /// Called with an event dict after every successful mutation
+    on_change: Option<Py<PyAny>>,
+    /// Next insertion sequence number, see `SEQ_FIELD`
+    next_seq: AtomicU64,
+    /// Soft-deleted records in the store, so queries know how far to overfetch
+    ///
+    /// vecstore only counts them with a full scan. Kept in step by
+    /// `soft_delete`, `undelete` and `purge_soft_deleted`; an upsert or
+    /// `rm` of a soft-deleted id leaves it too high until the next purge,
+    /// which only costs a few extra candidates.
+    soft_deleted: Arc<AtomicUsize>,
+    read_lock_stats: LockStats,
+    write_lock_stats: LockStats,
+    /// Write-ahead log, see `VectorStore.open(wal=True)`
+    wal: Option<wal::Wal>,
 }
 
-#[pymethods]
-impl VectorStore {
-    /// Create a new VectorStore instance
-    /// 
-    /// Args:
-    ///     dimension: Vector dimension (e.g., 768 for most embedding models)
-    #[new]
-    fn new(dimension: usize) -> PyResult<Self> {
-        // Create a temporary directory for the vector store
-        let temp_dir = std::env::temp_dir().join(format!("tf_vecstore_{}", uuid::Uuid::new_v4()));
-        std::fs::create_dir_all(&temp_dir).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to create temp directory: {}",
-                e
-            ))
+/// Run a k-nearest-neighbor query on a locked store, see `VectorStore::run_query`
+///
+/// The lock is released as soon as the backend returns. `start` is when
+/// the caller began, so slow-query logging includes the lock wait.
+fn ann_query(
+    store: RwLockReadGuard<'_, VecStore>,
+    metric: Distance,
+    vector: Vec<f32>,
+    k: usize,
+    soft_deleted: usize,
+    start: Instant,
+) -> PyResult<Vec<Neighbor>> {
+    // Soft-deleted records still occupy index slots and are skipped after
+    // the ANN fetch, so ask for enough extra candidates to still fill k
+    let query = Query {
+        vector,
+        k: k.saturating_add(soft_deleted),
+        filter: None,
+    };
+    let mut results = store
+        .query(query)
+        .map_err(|e| {
+            error!("Search failed (k={}): {}", k, e);
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Search failed: {}", e))
         })?;
+    drop(store);
+    results.truncate(k);
+
+    let elapsed = start.elapsed();
+    if elapsed >= SLOW_QUERY {
+        warn!("Slow query: k={} took {:.1} ms", k, elapsed.as_secs_f64() * 1000.0);
+    } else {
+        debug!("Query k={} took {:.3} ms", k, elapsed.as_secs_f64() * 1000.0);
+    }
+
+    for result in &mut results {
+        result.score = scoring::from_backend_score(metric, result.score);
+    }
+
+    Ok(results)
+}
+
+/// What a scan needs from a `VectorStore` to run with the GIL released
+///
+/// Once `py.detach` lets other Python threads run, any of them can call a
+/// `&mut self` method on the same store, which fails with "Already
+/// borrowed" while a `&self` call is still in progress. Scans that detach
+/// therefore copy this handle out, drop their borrow of the Python object
+/// and work only through the handle; concurrent writers then wait on the
+/// store's RwLock instead of failing.
+struct Detached {
+    store: Arc<RwLock<VecStore>>,
+    pool: Option<Arc<rayon::ThreadPool>>,
+    metric: Distance,
+    id_type: IdType,
+    soft_deleted: Arc<AtomicUsize>,
+}
+
+impl Detached {
+    /// Acquire the read lock, mapping poisoning to a Python error
+    fn read_store(&self) -> PyResult<RwLockReadGuard<'_, VecStore>> {
+        self.store.read().map_err(|e| {
+            error!("Read lock poisoned: {}", e);
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })
+    }
+
+    /// Run a parallel computation on the store's thread pool
+    fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// `VectorStore::run_query` for vectors whose dimension is already known to match
+    fn run_query(&self, vector: Vec<f32>, k: usize) -> PyResult<Vec<Neighbor>> {
+        let start = Instant::now();
+        let soft_deleted = self.soft_deleted.load(Ordering::Relaxed);
+        ann_query(self.read_store()?, self.metric, vector, k, soft_deleted, start)
+    }
+
+    /// Exact top-k by sparse dot product over records sharing an index with the query
+    fn sparse_neighbors(&self, py: Python, query: &sparse::SparseVector, k: usize) -> PyResult<Vec<Neighbor>> {
+        let records = self.read_store()?.list_active();
+        let mut scored: Vec<Neighbor> = py.detach(|| {
+            self.install(|| {
+                records
+                    .into_par_iter()
+                    .filter_map(|record| {
+                        let score = sparse::SparseVector::from_metadata(&record.metadata)?.dot(query)?;
+                        Some(Neighbor {
+                            id: record.id,
+                            score,
+                            metadata: record.metadata,
+                        })
+                    })
+                    .collect()
+            })
+        });
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+        scored.truncate(k);
+        Ok(scored)
+    }
+}
+
+impl VectorStore {
+    /// Open the VecStore backed by `root` and wrap it
+    ///
+    /// The dimension persisted in the store's manifest wins: when `dimension`
+    /// is None it is read from the manifest, otherwise it is validated against
+    /// it. When `temp` is true the directory is removed when the store is
+    /// dropped.
+    fn from_dir(
+        root: PathBuf,
+        dimension: Option<usize>,
+        temp: bool,
+        options: StoreOptions,
+    ) -> PyResult<Self> {
+        let discard = |root: &Path| {
+            if temp {
+                let _ = std::fs::remove_dir_all(root);
+            }
+        };
+
+        let pool = match options.num_threads.map(build_pool).transpose() {
+            Ok(pool) => pool.map(Arc::new),
+            Err(e) => {
+                discard(&root);
+                return Err(e);
+            }
+        };
+
+        let store = match VecStore::open(&root) {
+            Ok(store) => store,
+            Err(e) => {
+                discard(&root);
+                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to create vector store: {}",
+                    e
+                )));
+            }
+        };
+
+        // VecStore reports 0 until it has seen a vector
+        let stored = store.dimension();
+        let dimension = match (dimension, stored) {
+            (Some(d), 0) => d,
+            (Some(d), s) if d != s => {
+                discard(&root);
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Dimension mismatch. Store at {} has dimension {}, got {}",
+                    root.display(),
+                    s,
+                    d
+                )));
+            }
+            (_, 0) => {
+                discard(&root);
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "No dimension found in manifest at {}; pass dimension explicitly",
+                    root.display()
+                )));
+            }
+            (_, s) => s,
+        };
+
+        let metric = store.distance_metric();
+        let deleted = store.list_deleted();
+        let next_seq = store
+            .list_active()
+            .iter()
+            .chain(&deleted)
+            .filter_map(|r| r.metadata.fields.get(SEQ_FIELD)?.as_u64())
+            .max()
+            .map_or(0, |seq| seq + 1);
+
+        Ok(VectorStore {
+            store: Some(Arc::new(RwLock::new(store))),
+            dimension,
+            temp_path: if temp { Some(root.clone()) } else { None },
+            root,
+            id_type: options.id_type,
+            readonly: options.readonly,
+            content_mode: options.content_mode,
+            compression_level: options.compression_level,
+            pool,
+            check_dimensions: options.check_dimensions,
+            normalize: options.normalize,
+            retries: options.retries,
+            retry_backoff: Duration::from_millis(options.retry_backoff_ms),
+            id_normalizer: options.id_normalizer,
+            storage_dtype: options.storage_dtype,
+            schema: options.schema,
+            metric,
+            on_change: None,
+            next_seq: AtomicU64::new(next_seq),
+            soft_deleted: Arc::new(AtomicUsize::new(deleted.len())),
+            read_lock_stats: LockStats::default(),
+            write_lock_stats: LockStats::default(),
+            wal: None,
+        })
+    }
+
+    /// Take the next insertion sequence number
+    fn next_seq(&self) -> u64 {
+        self.next_seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The underlying store, or an error once `close()` has run
+    fn open_store(&self) -> PyResult<&RwLock<VecStore>> {
+        self.store
+            .as_deref()
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Store is closed"))
+    }
+
+    /// Acquire the read lock, mapping poisoning to a Python error
+    fn read_store(&self) -> PyResult<RwLockReadGuard<'_, VecStore>> {
+        let store = self.open_store()?;
+        let guard = match store.try_read() {
+            Ok(guard) => {
+                self.read_lock_stats.record(None);
+                Ok(guard)
+            }
+            Err(TryLockError::Poisoned(e)) => Err(e),
+            Err(TryLockError::WouldBlock) => {
+                let start = Instant::now();
+                let guard = store.read();
+                self.read_lock_stats.record(Some(start.elapsed()));
+                log_lock_wait("read", start.elapsed());
+                guard
+            }
+        };
+        guard.map_err(|e| {
+            error!("Read lock poisoned: {}", e);
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })
+    }
+
+    /// Acquire the write lock, mapping poisoning to a Python error
+    fn write_store(&self) -> PyResult<RwLockWriteGuard<'_, VecStore>> {
+        let store = self.open_store()?;
+        let guard = match store.try_write() {
+            Ok(guard) => {
+                self.write_lock_stats.record(None);
+                Ok(guard)
+            }
+            Err(TryLockError::Poisoned(e)) => Err(e),
+            Err(TryLockError::WouldBlock) => {
+                let start = Instant::now();
+                let guard = store.write();
+                self.write_lock_stats.record(Some(start.elapsed()));
+                log_lock_wait("write", start.elapsed());
+                guard
+            }
+        };
+        guard.map_err(|e| {
+            error!("Write lock poisoned: {}", e);
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e))
+        })
+    }
+
+    /// Reject mutations on a readonly store before any lock is taken
+    fn ensure_writable(&self) -> PyResult<()> {
+        if self.readonly {
+            return Err(PyErr::new::<pyo3::exceptions::PyPermissionError, _>(
+                "Store is readonly",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate metadata about to be written against the store's schema
+    fn check_schema(&self, metadata: &Metadata) -> PyResult<()> {
+        match &self.schema {
+            Some(schema) => schema.check(metadata),
+            None => Ok(()),
+        }
+    }
+
+    /// A handle for work done with the GIL released, see `Detached`
+    fn detached(&self) -> PyResult<Detached> {
+        Ok(Detached {
+            store: Arc::clone(self.store.as_ref().ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Store is closed")
+            })?),
+            pool: self.pool.clone(),
+            metric: self.metric,
+            id_type: self.id_type,
+            soft_deleted: Arc::clone(&self.soft_deleted),
+        })
+    }
+
+    /// Run a parallel computation on this store's thread pool
+    fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// Ensure a vector has the dimension this store was created with
+    fn check_dimension(&self, vector: &[f32]) -> PyResult<()> {
+        if vector.len() != self.dimension {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Vector dimension mismatch. Expected {}, got {}",
+                self.dimension,
+                vector.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Run a VecStore disk operation, retrying transient I/O errors
+    ///
+    /// Waits `retry_backoff`, doubling after every attempt, up to `retries`
+    /// times before returning the last error. Any lock held by the caller
+    /// stays held while waiting.
+    fn with_retry<T, E>(&self, what: &str, mut op: impl FnMut() -> Result<T, E>) -> Result<T, E>
+    where
+        E: std::ops::Deref<Target = dyn std::error::Error + Send + Sync + 'static>,
+    {
+        let mut backoff = self.retry_backoff;
+        for attempt in 1..=self.retries {
+            match op() {
+                Err(e) if is_transient(&*e) => {
+                    warn!(
+                        "{} failed (attempt {} of {}), retrying in {:?}: {}",
+                        what,
+                        attempt,
+                        self.retries + 1,
+                        backoff,
+                        &*e
+                    );
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+        op()
+    }
+
+    /// Apply the store-level `normalize` setting to an incoming vector
+    fn normalize_incoming(&self, vector: &mut [f32]) {
+        if self.normalize {
+            scoring::normalize(vector);
+        }
+    }
+
+    /// Validate the query vector and run a k-nearest-neighbor query
+    ///
+    /// Results are already sorted by vecstore (highest score first). Backend
+    /// scores are converted to similarities so they match the brute-force
+    /// scorer in `scoring`.
+    fn run_query(&self, vector: Vec<f32>, k: usize) -> PyResult<Vec<Neighbor>> {
+        if self.check_dimensions {
+            self.check_dimension(&vector)?;
+        }
+
+        // Execute query with read lock for concurrent access
+        let start = Instant::now();
+        let store = self.read_store()?;
+        let soft_deleted = self.soft_deleted.load(Ordering::Relaxed);
+        ann_query(store, self.metric, vector, k, soft_deleted, start)
+    }
+
+    /// Score the given ids against a query, for pinning them into results
+    ///
+    /// Keeps the given order, drops duplicates and ids that are not active.
+    fn pinned_neighbors(&self, keys: &[String], query: &[f32]) -> PyResult<Vec<Neighbor>> {
+        let wanted: HashSet<&str> = keys.iter().map(String::as_str).collect();
+        let mut records: HashMap<String, Record> = self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .filter(|r| wanted.contains(r.id.as_str()))
+            .map(|r| (r.id.clone(), r))
+            .collect();
+
+        Ok(keys
+            .iter()
+            .filter_map(|key| records.remove(key))
+            .map(|record| Neighbor {
+                id: record.id,
+                score: scoring::similarity(self.metric, query, &record.vector),
+                metadata: record.metadata,
+            })
+            .collect())
+    }
+
+
+    /// Fetch the stored vectors of the given ids; missing ids are absent from the map
+    fn lookup_vectors<'a>(&self, keys: impl Iterator<Item = &'a str>) -> PyResult<HashMap<String, Vec<f32>>> {
+        let wanted: HashSet<&str> = keys.collect();
+        Ok(self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .filter(|r| wanted.contains(r.id.as_str()))
+            .map(|r| (r.id, r.vector))
+            .collect())
+    }
+
+    /// Report a successful mutation to the write-ahead log and the
+    /// `on_change` callback, if they are set
+    ///
+    /// Callers must release the write lock first so the callback never runs
+    /// while the store is locked.
+    fn notify(&self, py: Python, op: &str, key: Option<&str>) -> PyResult<()> {
+        self.log_changes(op, key.as_slice())?;
+        self.fire_change(py, op, key)
+    }
+
+    /// `notify` for a batch of ids: one write-ahead log sync, one event per id
+    fn notify_many(&self, py: Python, op: &str, keys: &[&str]) -> PyResult<()> {
+        self.log_changes(op, keys)?;
+        for key in keys {
+            self.fire_change(py, op, Some(key))?;
+        }
+        Ok(())
+    }
+
+    /// Append the current state of the changed records to the write-ahead log
+    ///
+    /// A whole-store change ("replace_all") is checkpointed instead.
+    fn log_changes(&self, op: &str, keys: &[&str]) -> PyResult<()> {
+        let Some(log) = &self.wal else {
+            return Ok(());
+        };
+        let entries: Vec<wal::WalEntry> = match op {
+            "replace_all" => {
+                self.save()?;
+                return log.truncate();
+            }
+            "delete" => keys.iter().map(|id| wal::WalEntry::Delete { id: id.to_string() }).collect(),
+            "soft_delete" => keys.iter().map(|id| wal::WalEntry::SoftDelete { id: id.to_string() }).collect(),
+            "undelete" => keys.iter().map(|id| wal::WalEntry::Undelete { id: id.to_string() }).collect(),
+            _ => {
+                let wanted: HashSet<&str> = keys.iter().copied().collect();
+                self.read_store()?
+                    .list_active()
+                    .into_iter()
+                    .filter(|r| wanted.contains(r.id.as_str()))
+                    .map(|r| match op {
+                        "update" => wal::WalEntry::Update {
+                            id: r.id,
+                            metadata: r.metadata,
+                        },
+                        _ => wal::WalEntry::Upsert {
+                            id: r.id,
+                            vector: r.vector,
+                            metadata: r.metadata,
+                        },
+                    })
+                    .collect()
+            }
+        };
+        log.append(&entries)
+    }
+
+    /// Call the `on_change` callback, if one is set
+    fn fire_change(&self, py: Python, op: &str, key: Option<&str>) -> PyResult<()> {
+        let Some(callback) = &self.on_change else {
+            return Ok(());
+        };
+        let event = PyDict::new(py);
+        event.set_item("op", op)?;
+        match key {
+            Some(key) => event.set_item("id", self.key_to_py(py, key)?)?,
+            None => event.set_item("id", py.None())?,
+        }
+        callback.call1(py, (event,))?;
+        Ok(())
+    }
+
+    /// Build a fresh VecStore from `entries` and swap it in under the write lock
+    ///
+    /// Readers see either the old or the new contents, never a partial store.
+    /// Temporary stores move to a new temp directory; persistent stores save
+    /// the replacement next to the store directory and move it into place.
+    fn swap_in(&mut self, entries: Vec<(String, Vec<f32>, Metadata)>) -> PyResult<()> {
+        let staging = match self.temp_path {
+            Some(_) => create_temp_dir()?,
+            None => self.root.with_file_name(format!(
+                "{}.replace-{}",
+                self.root.file_name().and_then(|n| n.to_str()).unwrap_or("store"),
+                uuid::Uuid::new_v4()
+            )),
+        };
+        let build = || -> Result<VecStore, String> {
+            let mut fresh = VecStore::open(&staging).map_err(|e| e.to_string())?;
+            for (key, vector, metadata) in entries {
+                fresh.upsert(key, vector, metadata).map_err(|e| e.to_string())?;
+            }
+            Ok(fresh)
+        };
+        let fresh = match build() {
+            Ok(fresh) => fresh,
+            Err(e) => {
+                error!("Failed to build replacement store, old contents kept: {}", e);
+                let _ = std::fs::remove_dir_all(&staging);
+                return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to build replacement store: {}",
+                    e
+                )));
+            }
+        };
+
+        if self.temp_path.is_some() {
+            *self.write_store()? = fresh;
+            self.soft_deleted.store(0, Ordering::Relaxed);
+            let old_root = std::mem::replace(&mut self.root, staging.clone());
+            self.temp_path = Some(staging);
+            let _ = std::fs::remove_dir_all(old_root);
+            return Ok(());
+        }
+
+        // Persistent store: the VecStore is bound to its directory, so save
+        // the replacement and move it into place before reopening
+        let io_error = |e: String| {
+            error!("Failed to replace store at {}: {}", self.root.display(), e);
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to replace store at {}: {}",
+                self.root.display(),
+                e
+            ))
+        };
+        if let Err(e) = self.with_retry("Saving replacement store", || fresh.save()) {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(io_error(e.to_string()));
+        }
+        drop(fresh);
+
+        let mut store = self.write_store()?;
+        let backup = PathBuf::from(format!("{}.old", staging.display()));
+        std::fs::rename(&self.root, &backup).map_err(|e| io_error(e.to_string()))?;
+        if let Err(e) = std::fs::rename(&staging, &self.root) {
+            let _ = std::fs::rename(&backup, &self.root);
+            return Err(io_error(e.to_string()));
+        }
+        *store = self
+            .with_retry("Reopening store", || VecStore::open(&self.root))
+            .map_err(|e| io_error(e.to_string()))?;
+        self.soft_deleted.store(0, Ordering::Relaxed);
+        drop(store);
+        let _ = std::fs::remove_dir_all(backup);
+
+        // The open log handle belongs to the old directory, now deleted
+        if self.wal.is_some() {
+            self.wal = Some(wal::Wal::open(&self.root)?);
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the `k` argument of a search: default 5, reject 0
+    ///
+    /// A k larger than the store needs no clamping here: vecstore caps the
+    /// candidates at the number of records, and counting them is a full scan.
+    fn resolve_k(&self, k: Option<usize>) -> PyResult<usize> {
+        match k.unwrap_or(5) {
+            0 => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1")),
+            k => Ok(k),
+        }
+    }
+
+    /// Build a search result dict - only the fields we need, no vectors
+    fn result_dict<'py>(
+        &self,
+        py: Python<'py>,
+        key: &str,
+        score: f32,
+        metadata: &Metadata,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        result_dict(py, self.id_type, key, score, metadata)
+    }
+
+    /// Convert a Python id into the string key used by VecStore
+    ///
+    /// The `id_normalizer` callback, if any, runs first.
+    fn key_from_py(&self, id: &Bound<'_, PyAny>) -> PyResult<String> {
+        match &self.id_normalizer {
+            Some(normalizer) => self.id_type.key_from_py(&normalizer.bind(id.py()).call1((id,))?),
+            None => self.id_type.key_from_py(id),
+        }
+    }
+
+    /// Convert a VecStore key back into the Python id type of this store
+    fn key_to_py(&self, py: Python, key: &str) -> PyResult<Py<PyAny>> {
+        self.id_type.key_to_py(py, key)
+    }
+
+    /// Build a dict with the id and all public metadata fields of a record
+    fn record_dict<'py>(&self, py: Python<'py>, record: Record, include_vector: bool) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("id", self.key_to_py(py, &record.id)?)?;
+        for (field, value) in &record.metadata.fields {
+            if !field.starts_with(INTERNAL_PREFIX) {
+                dict.set_item(field, json_to_py(py, value)?)?;
+            }
+        }
+        if include_vector {
+            dict.set_item("vector", record.vector)?;
+        }
+        Ok(dict)
+    }
+}
+
+#[pymethods]
+impl VectorStore {
+    /// Create a new VectorStore instance
+    /// 
+    /// Args:
+    ///     dimension: Vector dimension (e.g., 768 for most embedding models)
+    ///     id_type: Type of document ids, "str" (default) or "int". Integer ids
+    ///         are accepted and returned as Python ints.
+    ///     readonly: If True, every mutating method raises PermissionError.
+    ///         This is enforced by the binding, not by filesystem permissions.
+    ///     store_content: "none" (default) discards content after
+    ///         vectorization. "compressed" keeps a gzip-compressed copy in
+    ///         metadata so `get`/`search` can return it with
+    ///         `include_content=True`, at a fraction of the raw text's memory.
+    ///     compression_level: Gzip level for store_content="compressed",
+    ///         0 (fastest) to 9 (smallest), default 6
+    ///     num_threads: Worker threads for the exhaustive (brute-force) code
+    ///         paths such as `evaluate_recall`. Default: rayon's automatic
+    ///         choice, usually one thread per core, shared by all stores.
+    ///     check_dimensions: If False, `set_vector` and `search` (and the other
+    ///         query methods) skip the vector length check. WARNING: passing a
+    ///         wrong-length vector with checks off is undefined behavior
+    ///         delegated to VecStore - it may raise, return meaningless
+    ///         scores, or panic. Only disable this in trusted, hot loops.
+    ///     normalize: If True, vectors written by `set`, `set_batch`,
+    ///         `set_vector`, `set_vectors_batch`, `update_vector` and
+    ///         `migrate_dimension` are unit-normalized first (zero vectors are
+    ///         left unchanged). Queries are not; see `normalize_query`.
+    ///     capacity: Expected final number of records, for bulk loads of a
+    ///         known size. VecStore 1.0 has no reserve hook: its HNSW graph is
+    ///         always pre-sized for 100,000 elements, so the hint cannot grow
+    ///         it. A larger capacity logs a warning (see `set_log_level`)
+    ///         because recall may degrade past that size; run `optimize()`
+    ///         after loading and check `evaluate_recall`.
+    ///     retries: Extra attempts for disk operations (`save`, `snapshot`
+    ///         and the store swap of `replace_all`/`optimize`) that fail with
+    ///         a transient I/O error (default: 0). Validation errors, missing
+    ///         files and permission errors are never retried. In-memory
+    ///         operations such as `set` and `search` do no I/O in VecStore 1.0
+    ///         and are not retried. The store stays locked while waiting.
+    ///     retry_backoff_ms: Delay before the first retry, doubled after each
+    ///         attempt (default: 50)
+    ///     id_normalizer: Callable applied to every id passed to the store
+    ///         (inserts, `get`, `rm`, `update`, `similarity`, ...) before it
+    ///         is used, e.g. `lambda s: s.lower().replace(" ", "")`. It must
+    ///         return a value of the store's id_type. Ids returned by the
+    ///         store are the normalized ones. Records inserted before the
+    ///         normalizer (or under a different one) keep their old ids and
+    ///         may become unreachable, so pick it once, at construction.
+    ///     storage_dtype: Precision every inserted vector is rounded to:
+    ///         "f32" (default), "f16" or "bf16". bf16 matches bfloat16 model
+    ///         outputs (e.g. PyTorch) exactly, keeping f32's range with about
+    ///         2-3 significant digits; f16 keeps about 3-4 digits but
+    ///         overflows above 65504. Queries stay f32 and all scoring is
+    ///         done in f32. VecStore holds f32 vectors, so no memory is saved.
+    ///         `set_vector(dtype=...)` overrides it per record. Input can be
+    ///         any sequence of floats, including a numpy `bfloat16` array
+    ///         (from ml_dtypes) or `tensor.tolist()`.
+    ///     schema: Dict of metadata field -> type (`str`, `int`, `float`,
+    ///         `bool`, `list`, `dict`, or their names as strings), e.g.
+    ///         `{"url": str, "published_at": float}`. Every write (`set`,
+    ///         `set_batch`, `update`, `replace_all`, ...) is checked first
+    ///         and raises ValueError on a mismatch; an int is a valid float.
+    ///         Fields not in the schema are unchecked.
+    ///     strict_schema: Also reject fields the schema does not declare,
+    ///         apart from title, url, summary and boost. Requires `schema`.
+    #[new]
+    #[pyo3(signature = (dimension, id_type = "str", readonly = false, store_content = "none", compression_level = 6, num_threads = None, check_dimensions = true, normalize = false, capacity = None, retries = 0, retry_backoff_ms = DEFAULT_RETRY_BACKOFF_MS, id_normalizer = None, storage_dtype = "f32", schema = None, strict_schema = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        dimension: usize,
+        id_type: &str,
+        readonly: bool,
+        store_content: &str,
+        compression_level: u32,
+        num_threads: Option<usize>,
+        check_dimensions: bool,
+        normalize: bool,
+        capacity: Option<usize>,
+        retries: usize,
+        retry_backoff_ms: u64,
+        id_normalizer: Option<Py<PyAny>>,
+        storage_dtype: &str,
+        schema: Option<Bound<'_, PyDict>>,
+        strict_schema: bool,
+    ) -> PyResult<Self> {
+        if compression_level > 9 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "compression_level must be between 0 and 9, got {}",
+                compression_level
+            )));
+        }
+        if let Some(capacity) = capacity.filter(|&c| c > BACKEND_PRESIZED_ELEMENTS) {
+            warn!(
+                "capacity {} exceeds the {} elements the HNSW index is pre-sized for; recall may degrade",
+                capacity, BACKEND_PRESIZED_ELEMENTS
+            );
+        }
+        let options = StoreOptions {
+            id_type: IdType::parse(id_type)?,
+            readonly,
+            content_mode: ContentMode::parse(store_content)?,
+            compression_level,
+            num_threads,
+            check_dimensions,
+            normalize,
+            retries,
+            retry_backoff_ms,
+            id_normalizer,
+            storage_dtype: VectorDtype::parse(storage_dtype)?,
+            schema: match schema {
+                Some(schema) => Some(schema::Schema::parse(&schema, strict_schema)?),
+                None if strict_schema => {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                        "strict_schema requires a schema",
+                    ));
+                }
+                None => None,
+            },
+        };
+
+        // Create a temporary directory for the vector store
+        let temp_dir = create_temp_dir()?;
+
+        Self::from_dir(temp_dir, Some(dimension), true, options)
+    }
+
+    /// Open (or create) a persistent store in a directory
+    ///
+    /// Unlike `VectorStore(dimension)`, the directory is kept when the store
+    /// is dropped. Call `save()` to persist changes. The dimension and
+    /// distance metric are read from the store's on-disk manifest.
+    ///
+    /// Args:
+    ///     path: Directory backing the store
+    ///     dimension: Vector dimension. If None it is read from the manifest,
+    ///         which must then exist; if given it must match the manifest.
+    ///     id_type: Type of document ids, "str" (default) or "int"
+    ///     readonly: If True, every mutating method raises PermissionError
+    ///     retries: Extra attempts for disk operations failing with a
+    ///         transient I/O error, see `VectorStore(retries=...)`
+    ///     retry_backoff_ms: Delay before the first retry, doubled after each
+    ///         attempt (default: 50)
+    ///     id_normalizer: Callable applied to every incoming id, see
+    ///         `VectorStore(id_normalizer=...)`. Use the same one every time
+    ///         the directory is opened, or existing records can be orphaned.
+    ///     storage_dtype: Precision of vectors inserted from now on, see
+    ///         `VectorStore(storage_dtype=...)`. Existing records keep theirs.
+    ///     wal: Keep a write-ahead log (`wal.log` in the directory) so that
+    ///         changes made after the last save survive a crash. Every
+    ///         mutation appends the touched records and syncs the log before
+    ///         returning, which costs one fsync per call (batch methods write
+    ///         one entry per record but sync once) and a scan of the store
+    ///         per call to capture the records. `checkpoint()` saves and
+    ///         empties the log. Whatever the flag, a non-empty log left by a
+    ///         previous session is replayed on open and, unless readonly, the
+    ///         recovered store is saved and the log emptied.
+    #[staticmethod]
+    #[pyo3(signature = (path, dimension = None, id_type = "str", readonly = false, retries = 0, retry_backoff_ms = DEFAULT_RETRY_BACKOFF_MS, id_normalizer = None, storage_dtype = "f32", wal = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn open(
+        path: PathBuf,
+        dimension: Option<usize>,
+        id_type: &str,
+        readonly: bool,
+        retries: usize,
+        retry_backoff_ms: u64,
+        id_normalizer: Option<Py<PyAny>>,
+        storage_dtype: &str,
+        wal: bool,
+    ) -> PyResult<Self> {
+        let options = StoreOptions {
+            id_type: IdType::parse(id_type)?,
+            readonly,
+            retries,
+            retry_backoff_ms,
+            id_normalizer,
+            storage_dtype: VectorDtype::parse(storage_dtype)?,
+            ..StoreOptions::default()
+        };
+
+        // Don't let VecStore create an empty directory we can't use anyway
+        if dimension.is_none() && !path.join("manifest.json").exists() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "No manifest found at {}; pass dimension explicitly",
+                path.display()
+            )));
+        }
+
+        let mut store = Self::from_dir(path, dimension, false, options)?;
+        let replayed = wal::replay(&store.root, &mut *store.write_store()?)?;
+        if replayed > 0 {
+            let deleted = store.read_store()?.deleted_count();
+            store.soft_deleted.store(deleted, Ordering::Relaxed);
+            warn!("Recovered {} write-ahead log entries in {}", replayed, store.root.display());
+        }
+        if !readonly {
+            let log = wal::Wal::open(&store.root)?;
+            if replayed > 0 {
+                store.save()?;
+                log.truncate()?;
+            }
+            store.wal = wal.then_some(log);
+        }
+        Ok(store)
+    }
+
+    /// Restore a store from a tarball written by `snapshot()`
+    ///
+    /// The archive is unpacked into a fresh temporary directory, which is
+    /// removed when the store is dropped. Gzip-compressed archives are
+    /// detected automatically.
+    ///
+    /// Args:
+    ///     tar_path: Path of the `.tar` or `.tar.gz` snapshot
+    ///     dimension: Vector dimension; must match the snapshot
+    ///     id_type: Type of document ids, "str" (default) or "int"
+    ///     readonly: If True, every mutating method raises PermissionError
+    #[staticmethod]
+    #[pyo3(signature = (tar_path, dimension, id_type = "str", readonly = false))]
+    fn restore(tar_path: PathBuf, dimension: usize, id_type: &str, readonly: bool) -> PyResult<Self> {
+        let options = StoreOptions {
+            id_type: IdType::parse(id_type)?,
+            readonly,
+            ..StoreOptions::default()
+        };
+        let temp_dir = create_temp_dir()?;
+
+        if let Err(e) = unpack_archive(&tar_path, &temp_dir) {
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            return Err(PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to unpack snapshot {}: {}",
+                tar_path.display(),
+                e
+            )));
+        }
+
+        Self::from_dir(temp_dir, Some(dimension), true, options)
+    }
+
+    /// Persist the store to its backing directory
+    ///
+    /// Only meaningful for stores created with `VectorStore.open(path)`;
+    /// temporary stores are removed when dropped.
+    fn save(&self) -> PyResult<()> {
+        let store = self.read_store()?;
+        self.with_retry("Saving store", || store.save()).map_err(|e| {
+            error!("Failed to save store at {}: {}", self.root.display(), e);
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to save store: {}",
+                e
+            ))
+        })
+    }
+
+    /// Release the store now instead of when it is garbage collected
+    ///
+    /// Drops the in-memory index and records and, for temporary stores,
+    /// removes the backing directory. Unsaved changes to a store created
+    /// with `open()` are discarded, so call `save()` first. Afterwards every
+    /// method that reads or writes records raises RuntimeError("Store is
+    /// closed"). Calling `close()` again does nothing.
+    fn close(&mut self) {
+        if self.store.take().is_none() {
+            return;
+        }
+        self.pool = None;
+        if let Some(path) = self.temp_path.take() {
+            let _ = std::fs::remove_dir_all(path);
+        }
+        debug!("Closed store at {}", self.root.display());
+    }
+
+    /// Report lock contention counters since the store was created
+    ///
+    /// Every method that touches records takes the store's read or write
+    /// lock; a `contended` acquisition found it held and waited (waits over
+    /// 50 ms are also logged as warnings). Calls hold the GIL while they
+    /// take a lock, so writers never queue behind each other and there is
+    /// nothing to throttle. The exception is the scans that release the
+    /// GIL (`coverage`, `knn_graph`, `benchmark`, `similarity_matrix`,
+    /// `search_sparse`, ...): they take the read lock from another thread
+    /// while other calls, writes included, go ahead. A write that finds
+    /// the lock held by such a scan waits for it (counted here as a
+    /// contended `write_lock` acquisition) rather than failing.
+    ///
+    /// Returns:
+    ///     Dict with "read_lock" and "write_lock", each a dict of
+    ///     "acquisitions", "contended", "total_wait_ms" and "max_wait_ms"
+    fn stats(&self, py: Python) -> PyResult<Py<PyDict>> {
+        let stats = PyDict::new(py);
+        stats.set_item("read_lock", self.read_lock_stats.to_dict(py)?)?;
+        stats.set_item("write_lock", self.write_lock_stats.to_dict(py)?)?;
+        Ok(stats.into())
+    }
+
+    /// Whether `close()` has been called
+    #[getter]
+    fn closed(&self) -> bool {
+        self.store.is_none()
+    }
+
+    /// Save the store and empty its write-ahead log
+    ///
+    /// For stores opened with `wal=True`; on other stores this is `save()`.
+    /// Until a checkpoint the log keeps growing, and every entry in it is
+    /// replayed on the next `open` after a crash, so checkpoint regularly
+    /// (e.g. after each ingestion batch).
+    fn checkpoint(&self) -> PyResult<()> {
+        self.save()?;
+        match &self.wal {
+            Some(log) => log.truncate(),
+            None => Ok(()),
+        }
+    }
+
+    /// Vector dimension of this store
+    #[getter]
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Total bytes used by the store's backing directory
+    ///
+    /// Sums the sizes of all files under the directory (the temporary one
+    /// for `VectorStore(...)`, the store path for `open()`). Changes not yet
+    /// written by `save()` are not counted.
+    ///
+    /// Returns:
+    ///     Size in bytes
+    ///
+    /// Raises:
+    ///     IOError: If the directory was removed or cannot be read
+    fn disk_usage(&self) -> PyResult<u64> {
+        dir_size(&self.root).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to measure {}: {}",
+                self.root.display(),
+                e
+            ))
+        })
+    }
+
+    /// Name of the distance metric used by this store (e.g. "Cosine")
+    #[getter]
+    fn metric(&self) -> &'static str {
+        self.metric.name()
+    }
+
+    /// Archive the backing directory into a tarball
+    ///
+    /// The store is saved and archived while holding the write lock, so the
+    /// archive is a consistent image even with concurrent writers. Paths
+    /// ending in `.gz` or `.tgz` are gzip-compressed.
+    ///
+    /// Args:
+    ///     tar_path: Destination path of the archive
+    fn snapshot(&self, tar_path: PathBuf) -> PyResult<()> {
+        let store = self.write_store()?;
+        self.with_retry("Saving store", || store.save()).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to save store: {}",
+                e
+            ))
+        })?;
+
+        write_archive(&tar_path, &self.root).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to write snapshot {}: {}",
+                tar_path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Set (add/update) a document using Python callback for vectorization
+    /// 
+    /// This is a memory-efficient method that:
+    /// 1. Calls the Python callback function with the content
+    /// 2. Gets the vector from the callback
+    /// 3. Stores only the vector and metadata (title, url, summary)
+    /// 4. Discards the content after vectorization
+    /// 
+    /// Args:
+    ///     id: Unique identifier for the document
+    ///     content: Document content (will be vectorized via callback then discarded)
+    ///     title: Document title (stored)
+    ///     url: Document URL (stored)
+    ///     summary: Document summary (stored, optional)
+    ///     embedding_callback: Python callable that takes content and returns vector
+    #[allow(clippy::too_many_arguments)]
+    fn set(
+        &mut self,
+        py: Python,
+        id: &Bound<'_, PyAny>,
+        content: String,
+        title: String,
+        url: String,
+        summary: String,
+        embedding_callback: Py<PyAny>,
+    ) -> PyResult<()> {
+        self.ensure_writable()?;
+        let id = self.key_from_py(id)?;
+
+        let stored_content = match self.content_mode {
+            ContentMode::Compressed => Some(compress_content(&content, self.compression_level)?),
+            ContentMode::Discard => None,
+        };
+
+        // Call Python callback to get embedding vector
+        let mut vector: Vec<f32> = embedding_callback.call1(py, (content,))?.extract(py)?;
+
+        // Validate vector dimension
+        self.check_dimension(&vector)?;
+        self.normalize_incoming(&mut vector);
+        let vector = self.storage_dtype.quantize(vector);
+
+        // Create metadata - store title, url, and summary, NOT content
+        // This is the key to memory efficiency!
+        let mut metadata = document_metadata(&title, &url, Some(&summary), self.next_seq());
+        self.storage_dtype.mark(&mut metadata);
+        if let Some(compressed) = stored_content {
+            metadata.fields.insert(CONTENT_FIELD.to_string(), json!(compressed));
+        }
+        self.check_schema(&metadata)?;
+
+        // Upsert vector with metadata
+        // After this point, content is dropped and memory is freed
+        self.write_store()?
+            .upsert(id.clone(), vector, metadata)
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to add vector: {}",
+                    e
+                ))
+            })?;
+
+        self.notify(py, "upsert", Some(&id))
+    }
+
+    /// Set a document whose content is read from a file
+    ///
+    /// The file is read as UTF-8 text and handled exactly like `set`: the
+    /// content is passed to the callback and then discarded.
+    ///
+    /// Args:
+    ///     id: Unique identifier for the document
+    ///     path: Path of the file holding the document content
+    ///     title: Document title (stored)
+    ///     url: Document URL (stored)
+    ///     embedding_callback: Python callable that takes content and returns vector
+    ///     summary: Document summary (stored, default: "")
+    ///
+    /// Raises:
+    ///     IOError: If the file cannot be read or is not valid UTF-8
+    #[pyo3(signature = (id, path, title, url, embedding_callback, summary = String::new()))]
+    #[allow(clippy::too_many_arguments)]
+    fn set_from_file(
+        &mut self,
+        py: Python,
+        id: &Bound<'_, PyAny>,
+        path: PathBuf,
+        title: String,
+        url: String,
+        embedding_callback: Py<PyAny>,
+        summary: String,
+    ) -> PyResult<()> {
+        self.ensure_writable()?;
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        self.set(py, id, content, title, url, summary, embedding_callback)
+    }
+
+    /// Set multiple documents with a single embedding callback invocation
+    ///
+    /// The callback receives the list of contents and must return one vector
+    /// per content, in the same order. Contents are discarded after
+    /// vectorization, exactly like `set`.
+    ///
+    /// Args:
+    ///     ids: Document identifiers
+    ///     contents: Document contents (vectorized via callback then discarded)
+    ///     titles: Document titles
+    ///     urls: Document URLs
+    ///     summaries: Document summaries (optional, stored as "" when omitted)
+    ///     embedding_callback: Python callable taking a list of contents and
+    ///         returning a list of vectors
+    ///     on_bad_vector: "error" (default) aborts the whole batch before
+    ///         anything is written if any vector has the wrong dimension;
+    ///         "skip" stores the valid items and skips the bad ones
+    ///
+    /// Returns:
+    ///     List of ids that were skipped because of a bad vector (always
+    ///     empty with on_bad_vector="error")
+    ///
+    /// Raises:
+    ///     ValueError: If the callback returns the wrong number of vectors,
+    ///         a vector is bad with on_bad_vector="error", or the metadata
+    ///         violates the schema; nothing is written
+    ///     RuntimeError: If the backend fails partway through; the items
+    ///         before the failing one stay written and are reported to the
+    ///         write-ahead log and `on_change` as usual
+    #[pyo3(signature = (ids, contents, titles, urls, summaries, embedding_callback, on_bad_vector = "error"))]
+    #[allow(clippy::too_many_arguments)]
+    fn set_batch(
+        &mut self,
+        py: Python,
+        ids: Vec<Bound<'_, PyAny>>,
+        contents: Vec<String>,
+        titles: Vec<String>,
+        urls: Vec<String>,
+        summaries: Option<Vec<String>>,
+        embedding_callback: Py<PyAny>,
+        on_bad_vector: &str,
+    ) -> PyResult<Py<PyList>> {
+        self.ensure_writable()?;
+        let policy = BadVectorPolicy::parse(on_bad_vector)?;
+
+        check_batch_lengths(&[
+            ("ids", Some(ids.len())),
+            ("contents", Some(contents.len())),
+            ("titles", Some(titles.len())),
+            ("urls", Some(urls.len())),
+            ("summaries", summaries.as_ref().map(Vec::len)),
+        ])?;
+        let n = ids.len();
+
+        let keys = ids.iter().map(|id| self.key_from_py(id)).collect::<PyResult<Vec<_>>>()?;
+
+        let stored_contents = match self.content_mode {
+            ContentMode::Compressed => Some(
+                contents
+                    .iter()
+                    .map(|c| compress_content(c, self.compression_level))
+                    .collect::<PyResult<Vec<_>>>()?,
+            ),
+            ContentMode::Discard => None,
+        };
+
+        // One callback call for the whole batch; contents are dropped afterwards
+        let vectors: Vec<Vec<f32>> = embedding_callback.call1(py, (contents,))?.extract(py)?;
+        if vectors.len() != n {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Embedding callback returned {} vectors for {} contents",
+                vectors.len(),
+                n
+            )));
+        }
+
+        // Validate everything up front so "error" never leaves a partial batch
+        let skipped = PyList::empty(py);
+        let mut accepted = Vec::with_capacity(n);
+        for (i, vector) in vectors.into_iter().enumerate() {
+            if vector.len() != self.dimension {
+                match policy {
+                    BadVectorPolicy::Error => {
+                        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                            "Vector dimension mismatch for id '{}'. Expected {}, got {}",
+                            keys[i],
+                            self.dimension,
+                            vector.len()
+                        )));
+                    }
+                    BadVectorPolicy::Skip => {
+                        skipped.append(&ids[i])?;
+                        continue;
+                    }
+                }
+            }
+
+            let summary = summaries.as_ref().map(|s| s[i].as_str()).unwrap_or("");
+            let mut metadata = document_metadata(&titles[i], &urls[i], Some(summary), self.next_seq());
+            self.storage_dtype.mark(&mut metadata);
+            if let Some(stored) = &stored_contents {
+                metadata.fields.insert(CONTENT_FIELD.to_string(), json!(stored[i]));
+            }
+            self.check_schema(&metadata)?;
+            accepted.push((i, vector, metadata));
+        }
+
+        let mut store = self.write_store()?;
+        let mut written = Vec::with_capacity(accepted.len());
+        let mut failure = None;
+        for (i, mut vector, metadata) in accepted {
+            self.normalize_incoming(&mut vector);
+            let vector = self.storage_dtype.quantize(vector);
+
+            if let Err(e) = store.upsert(keys[i].clone(), vector, metadata) {
+                failure = Some(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to add vector: {}",
+                    e
+                )));
+                break;
+            }
+            written.push(i);
+        }
+        drop(store);
+
+        // Items written before a backend failure stay written, so they are
+        // logged and reported before the error is raised
+        let written: Vec<&str> = written.into_iter().map(|i| keys[i].as_str()).collect();
+        self.notify_many(py, "upsert", &written)?;
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(skipped.into()),
+        }
+    }
+
+    /// Ingest documents from a Python iterator in fixed-size batches
+    ///
+    /// Pulls up to `batch_size` items, embeds and stores them with one
+    /// `set_batch` call, and drops them before pulling the next batch, so
+    /// only one batch is ever held in memory. Suited to unbounded streams
+    /// such as a network source or a generator over a large file.
+    ///
+    /// Each batch is all-or-nothing. If the iterator, the callback or an
+    /// item fails, the batches before it stay written and a RuntimeError
+    /// (chained to the original error) reports the position to resume
+    /// from: call again with a fresh iterator over the same source and
+    /// `start=position`. Items of the failed batch were already consumed
+    /// from the old iterator, so it cannot simply be passed in again.
+    ///
+    /// Args:
+    ///     items: Iterable of dicts with "id", "content", "title", "url"
+    ///         and an optional "summary", as accepted by `set`
+    ///     embedding_callback: Python callable taking a list of contents and
+    ///         returning a list of vectors, as in `set_batch`
+    ///     batch_size: Items per callback call and write (default: 64)
+    ///     progress_callback: Optional callable invoked as
+    ///         `progress_callback(done, None)` after each batch; total is
+    ///         None because a stream's length is unknown
+    ///     start: Number of leading items to skip without embedding them,
+    ///         to resume an interrupted ingestion (default: 0)
+    ///
+    /// Returns:
+    ///     Number of documents ingested by this call (skipped items excluded)
+    ///
+    /// Raises:
+    ///     ValueError: If batch_size is 0
+    ///     RuntimeError: If ingestion stopped mid-stream; the message gives
+    ///         the resume position
+    #[pyo3(signature = (items, embedding_callback, batch_size = 64, progress_callback = None, start = 0))]
+    fn ingest_stream(
+        &mut self,
+        py: Python,
+        items: &Bound<'_, PyAny>,
+        embedding_callback: Py<PyAny>,
+        batch_size: usize,
+        progress_callback: Option<Py<PyAny>>,
+        start: usize,
+    ) -> PyResult<usize> {
+        self.ensure_writable()?;
+        if batch_size == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "batch_size must be at least 1",
+            ));
+        }
+
+        // Items consumed from the iterator, including the skipped ones
+        let mut position = 0;
+        let mut ingested = 0;
+        let stopped = |position: usize, ingested: usize, cause: PyErr| {
+            // Let KeyboardInterrupt and friends through unchanged
+            if !cause.is_instance_of::<pyo3::exceptions::PyException>(py) {
+                return cause;
+            }
+            let err = PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "ingest_stream stopped at item {} after ingesting {} documents; resume with start={}: {}",
+                position, ingested, position, cause
+            ));
+            err.set_cause(py, Some(cause));
+            err
+        };
+
+        let mut iterator = items.try_iter()?;
+        for _ in 0..start {
+            match iterator.next() {
+                Some(Ok(_)) => position += 1,
+                Some(Err(e)) => return Err(stopped(position, ingested, e)),
+                None => return Ok(0),
+            }
+        }
+
+        let missing = |key: &str| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Item is missing '{}'", key))
+        };
+        loop {
+            let mut ids = Vec::with_capacity(batch_size);
+            let mut contents = Vec::with_capacity(batch_size);
+            let mut titles = Vec::with_capacity(batch_size);
+            let mut urls = Vec::with_capacity(batch_size);
+            let mut summaries = Vec::with_capacity(batch_size);
+            let batch: PyResult<()> = (|| {
+                while ids.len() < batch_size {
+                    let Some(item) = iterator.next() else { break };
+                    let item = item?;
+                    let item = item.cast::<PyDict>().map_err(|_| {
+                        PyErr::new::<pyo3::exceptions::PyValueError, _>("Items must be dicts")
+                    })?;
+                    ids.push(item.get_item("id")?.ok_or_else(|| missing("id"))?);
+                    contents.push(item.get_item("content")?.ok_or_else(|| missing("content"))?.extract::<String>()?);
+                    titles.push(item.get_item("title")?.ok_or_else(|| missing("title"))?.extract::<String>()?);
+                    urls.push(item.get_item("url")?.ok_or_else(|| missing("url"))?.extract::<String>()?);
+                    summaries.push(match item.get_item("summary")? {
+                        Some(summary) => summary.extract::<String>()?,
+                        None => String::new(),
+                    });
+                }
+                Ok(())
+            })();
+            batch.map_err(|e| stopped(position, ingested, e))?;
+            if ids.is_empty() {
+                break;
+            }
+
+            let n = ids.len();
+            self.set_batch(
+                py,
+                ids,
+                contents,
+                titles,
+                urls,
+                Some(summaries),
+                embedding_callback.clone_ref(py),
+                "error",
+            )
+            .map_err(|e| stopped(position, ingested, e))?;
+            position += n;
+            ingested += n;
+
+            if let Some(progress) = &progress_callback {
+                progress
+                    .call1(py, (ingested, py.None()))
+                    .map_err(|e| stopped(position, ingested, e))?;
+            }
+            if n < batch_size {
+                break;
+            }
+        }
+
+        info!("Ingested {} documents from stream", ingested);
+        Ok(ingested)
+    }
+
+    /// Set a document with pre-computed vector (for batch operations)
+    /// 
+    /// Use this when you already have the vector and don't need the callback.
+    /// 
+    /// Args:
+    ///     id: Unique identifier for the document
+    ///     vector: Pre-computed embedding vector
+    ///     title: Document title
+    ///     url: Document URL
+    ///     summary: Document summary (optional, stored as "" when omitted,
+    ///         so every record has the same fields as with `set`)
+    ///     dtype: Precision to keep this vector at: "f32", "f16" or "bf16"
+    ///         (default: the store's `storage_dtype`). Reduced-precision
+    ///         vectors are rounded and scored as f32; the backend still
+    ///         stores them as f32, so no memory is saved.
+    ///     prenormalized: If True, the vector is already unit length and the
+    ///         store-level `normalize` step is skipped for this call. The
+    ///         vector is stored as given; it is not checked.
+    #[pyo3(signature = (id, vector, title, url, summary = None, dtype = None, prenormalized = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn set_vector(
+        &mut self,
+        py: Python,
+        id: &Bound<'_, PyAny>,
+        mut vector: Vec<f32>,
+        title: String,
+        url: String,
+        summary: Option<String>,
+        dtype: Option<&str>,
+        prenormalized: bool,
+    ) -> PyResult<()> {
+        self.ensure_writable()?;
+        let id = self.key_from_py(id)?;
+        let dtype = match dtype {
+            Some(name) => VectorDtype::parse(name)?,
+            None => self.storage_dtype,
+        };
+
+        if self.check_dimensions {
+            self.check_dimension(&vector)?;
+        }
+        if !prenormalized {
+            self.normalize_incoming(&mut vector);
+        }
+        let vector = dtype.quantize(vector);
+
+        // Create metadata - title, url, and summary, no content
+        let mut metadata = document_metadata(&title, &url, summary.as_deref(), self.next_seq());
+        dtype.mark(&mut metadata);
+        self.check_schema(&metadata)?;
+
+        self.write_store()?
+            .upsert(id.clone(), vector, metadata)
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to add vector: {}",
+                    e
+                ))
+            })?;
+
+        self.notify(py, "upsert", Some(&id))
+    }
+
+    /// Set multiple documents with pre-computed vectors under one write lock
+    ///
+    /// All vectors, ids and metadata are validated before anything is
+    /// written.
+    ///
+    /// Args:
+    ///     ids: Document identifiers
+    ///     vectors: Pre-computed embedding vectors
+    ///     titles: Document titles
+    ///     urls: Document URLs
+    ///     summaries: Document summaries (optional, stored as "" when omitted)
+    ///     report_overwrites: If True, return the ids that already existed
+    ///         and were overwritten
+    ///     on_duplicate: How an id repeated within this batch is handled
+    ///         (ids already in the store are always overwritten):
+    ///         "last_wins" (default) writes every item so the last one
+    ///         sticks, "first_wins" skips the later items, and "error" raises
+    ///         before anything is written
+    ///
+    /// Returns:
+    ///     List of overwritten ids if report_overwrites is True, else None
+    ///
+    /// Raises:
+    ///     ValueError: If a vector has the wrong dimension, the metadata
+    ///         violates the schema, or on_duplicate="error" and an id is
+    ///         repeated; nothing is written
+    ///     RuntimeError: If the backend fails partway through; the items
+    ///         before the failing one stay written and are reported to the
+    ///         write-ahead log and `on_change` as usual
+    #[pyo3(signature = (ids, vectors, titles, urls, summaries = None, report_overwrites = false, on_duplicate = "last_wins"))]
+    #[allow(clippy::too_many_arguments)]
+    fn set_vectors_batch(
+        &mut self,
+        py: Python,
+        ids: Vec<Bound<'_, PyAny>>,
+        vectors: Vec<Vec<f32>>,
+        titles: Vec<String>,
+        urls: Vec<String>,
+        summaries: Option<Vec<String>>,
+        report_overwrites: bool,
+        on_duplicate: &str,
+    ) -> PyResult<Option<Py<PyList>>> {
+        self.ensure_writable()?;
+        let policy = DuplicatePolicy::parse(on_duplicate)?;
+
+        check_batch_lengths(&[
+            ("ids", Some(ids.len())),
+            ("vectors", Some(vectors.len())),
+            ("titles", Some(titles.len())),
+            ("urls", Some(urls.len())),
+            ("summaries", summaries.as_ref().map(Vec::len)),
+        ])?;
+
+        let keys = ids.iter().map(|id| self.key_from_py(id)).collect::<PyResult<Vec<_>>>()?;
+        for vector in &vectors {
+            self.check_dimension(vector)?;
+        }
+
+        // Mark repeated ids and check the schema up front so no error leaves
+        // a partial batch
+        let mut first_seen: HashMap<&str, usize> = HashMap::with_capacity(keys.len());
+        let mut write = vec![true; keys.len()];
+        for (i, key) in keys.iter().enumerate() {
+            let Some(&first) = first_seen.get(key.as_str()) else {
+                first_seen.insert(key, i);
+                continue;
+            };
+            match policy {
+                DuplicatePolicy::LastWins => {}
+                DuplicatePolicy::FirstWins => write[i] = false,
+                DuplicatePolicy::Error => {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "Duplicate id '{}' in batch at positions {} and {}",
+                        key, first, i
+                    )));
+                }
+            }
+        }
+        let mut metadatas = Vec::with_capacity(keys.len());
+        for i in 0..keys.len() {
+            if !write[i] {
+                metadatas.push(None);
+                continue;
+            }
+            let summary = summaries.as_ref().map(|s| s[i].as_str());
+            let mut metadata = document_metadata(&titles[i], &urls[i], summary, self.next_seq());
+            self.storage_dtype.mark(&mut metadata);
+            self.check_schema(&metadata)?;
+            metadatas.push(Some(metadata));
+        }
+
+        let mut store = self.write_store()?;
+
+        // Only pay for the id scan when collisions are requested
+        let existing: Option<HashSet<String>> = report_overwrites
+            .then(|| store.list_active().into_iter().map(|r| r.id).collect());
+        let overwritten = PyList::empty(py);
+
+        let mut written = Vec::with_capacity(keys.len());
+        let mut failure = None;
+        for (i, (mut vector, metadata)) in vectors.into_iter().zip(metadatas).enumerate() {
+            let Some(metadata) = metadata else {
+                continue;
+            };
+            self.normalize_incoming(&mut vector);
+            let vector = self.storage_dtype.quantize(vector);
+
+            if let Err(e) = store.upsert(keys[i].clone(), vector, metadata) {
+                failure = Some(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to add vector: {}",
+                    e
+                )));
+                break;
+            }
+            written.push(keys[i].as_str());
+            if existing.as_ref().is_some_and(|e| e.contains(&keys[i])) {
+                overwritten.append(&ids[i])?;
+            }
+        }
+        drop(store);
+
+        // Items written before a backend failure stay written, so they are
+        // logged and reported before the error is raised
+        self.notify_many(py, "upsert", &written)?;
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(report_overwrites.then(|| overwritten.unbind())),
+        }
+    }
+
+    /// Atomically replace the entire contents of the store
+    ///
+    /// The new dataset is built into a fresh VecStore first and swapped in
+    /// under the write lock, so concurrent readers see either the old or the
+    /// new contents, never a half-populated store. For stores created with
+    /// `open()` the replacement is saved and moved into the store directory.
+    ///
+    /// Args:
+    ///     records: List of dicts in the shape returned by
+    ///         `to_list(include_vectors=True)`: "id" and "vector" are
+    ///         required, every other key is stored as metadata
+    ///
+    /// Returns:
+    ///     Number of records in the store after the swap
+    fn replace_all(&mut self, py: Python, records: Vec<Bound<'_, PyDict>>) -> PyResult<usize> {
+        self.ensure_writable()?;
+
+        let missing = |key: &str| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Record is missing '{}'", key))
+        };
+        let mut entries = Vec::with_capacity(records.len());
+        for record in &records {
+            let key = self.key_from_py(&record.get_item("id")?.ok_or_else(|| missing("id"))?)?;
+            let vector: Vec<f32> = record.get_item("vector")?.ok_or_else(|| missing("vector"))?.extract()?;
+            self.check_dimension(&vector)?;
+
+            let mut metadata = Metadata {
+                fields: HashMap::new(),
+            };
+            for (field, value) in record.iter() {
+                let field: String = field.extract()?;
+                if field != "id" && field != "vector" {
+                    metadata.fields.insert(field, py_to_json(&value)?);
+                }
+            }
+            self.check_schema(&metadata)?;
+            metadata.fields.insert(SEQ_FIELD.to_string(), json!(self.next_seq()));
+            stamp_updated(&mut metadata);
+            self.storage_dtype.mark(&mut metadata);
+            entries.push((key, self.storage_dtype.quantize(vector), metadata));
+        }
+        let count = entries.len();
+
+        self.swap_in(entries)?;
+
+        self.notify(py, "replace_all", None)?;
+        Ok(count)
+    }
+
+    /// Re-embed every document into a new vector dimension (model upgrade)
+    ///
+    /// Requires a store created with `store_content="compressed"`, since the
+    /// content is needed to re-embed. Every record is re-embedded with the
+    /// callback and validated first; only then is a fresh store with the new
+    /// dimension swapped in (like `replace_all`), keeping ids and metadata.
+    /// If anything fails, the store is left untouched. Cached norms are
+    /// dropped and f16 records stay f16.
+    ///
+    /// Args:
+    ///     new_dimension: Dimension of the new embeddings
+    ///     reembed_callback: Python callable that takes content and returns
+    ///         a `new_dimension` vector
+    ///     progress_callback: Optional callable invoked as
+    ///         `progress_callback(done, total)` after each re-embedded record
+    ///
+    /// Returns:
+    ///     Number of migrated documents
+    ///
+    /// Raises:
+    ///     ValueError: If a document has no stored content or a callback
+    ///         vector has the wrong dimension
+    #[pyo3(signature = (new_dimension, reembed_callback, progress_callback = None))]
+    fn migrate_dimension(
+        &mut self,
+        py: Python,
+        new_dimension: usize,
+        reembed_callback: Py<PyAny>,
+        progress_callback: Option<Py<PyAny>>,
+    ) -> PyResult<usize> {
+        self.ensure_writable()?;
+        if new_dimension == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "new_dimension must be at least 1",
+            ));
+        }
+
+        let records = self.read_store()?.list_active();
+        let total = records.len();
+        let mut entries = Vec::with_capacity(total);
+        for (done, record) in records.into_iter().enumerate() {
+            let content = decompress_content(&record.metadata)?.ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Document '{}' has no stored content; migrate_dimension needs a store created with store_content=\"compressed\"",
+                    record.id
+                ))
+            })?;
+
+            let mut vector: Vec<f32> = reembed_callback.call1(py, (content,))?.extract(py)?;
+            if vector.len() != new_dimension {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Vector dimension mismatch for id '{}'. Expected {}, got {}",
+                    record.id,
+                    new_dimension,
+                    vector.len()
+                )));
+            }
+
+            self.normalize_incoming(&mut vector);
+            let mut metadata = record.metadata;
+            metadata.fields.remove(scoring::NORM_FIELD);
+            stamp_updated(&mut metadata);
+            let vector = VectorDtype::of(&metadata).quantize(vector);
+            entries.push((record.id, vector, metadata));
+
+            if let Some(progress) = &progress_callback {
+                progress.call1(py, (done + 1, total))?;
+            }
+        }
+
+        self.swap_in(entries)?;
+        info!("Migrated {} documents from dimension {} to {}", total, self.dimension, new_dimension);
+        self.dimension = new_dimension;
+
+        self.notify(py, "replace_all", None)?;
+        Ok(total)
+    }
+
+    /// Apply a function to every stored vector in place
+    ///
+    /// For embedding-space recalibration (mean-centering, rescaling, a
+    /// learned rotation) without re-ingesting documents. Each vector is
+    /// passed to the callback and the returned vector is validated; only
+    /// after every record has been transformed are the new vectors upserted,
+    /// so a failing callback or a rejected vector leaves the store
+    /// untouched. If the backend then fails during the upsert pass, the
+    /// records before the failing one keep their new vectors and
+    /// RuntimeError is raised. Metadata is kept, cached norms are
+    /// recomputed and f16 records stay f16. The `normalize` store option
+    /// applies to the returned vectors. This touches every record and holds
+    /// the write lock for the whole upsert pass. Once the upsert pass has
+    /// started, a "replace_all" change event fires (see `set_on_change`),
+    /// also when it fails partway.
+    ///
+    /// Args:
+    ///     callback: Python callable taking a vector (list of floats) and
+    ///         returning a vector of the same dimension
+    ///     progress_callback: Optional callable invoked as
+    ///         `progress_callback(done, total)` after each transformed record
+    ///
+    /// Returns:
+    ///     Number of transformed vectors
+    ///
+    /// Raises:
+    ///     ValueError: If the callback returns a vector of the wrong dimension
+    ///     RuntimeError: If the backend fails partway through the upsert pass
+    #[pyo3(signature = (callback, progress_callback = None))]
+    fn transform_vectors(
+        &mut self,
+        py: Python,
+        callback: Py<PyAny>,
+        progress_callback: Option<Py<PyAny>>,
+    ) -> PyResult<usize> {
+        self.ensure_writable()?;
+
+        let records = self.read_store()?.list_active();
+        let total = records.len();
+        let mut entries = Vec::with_capacity(total);
+        for (done, record) in records.into_iter().enumerate() {
+            let mut vector: Vec<f32> = callback.call1(py, (record.vector,))?.extract(py)?;
+            if vector.len() != self.dimension {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Vector dimension mismatch for id '{}'. Expected {}, got {}",
+                    record.id,
+                    self.dimension,
+                    vector.len()
+                )));
+            }
+
+            self.normalize_incoming(&mut vector);
+            let mut metadata = record.metadata;
+            let vector = VectorDtype::of(&metadata).quantize(vector);
+            if metadata.fields.contains_key(scoring::NORM_FIELD) {
+                metadata
+                    .fields
+                    .insert(scoring::NORM_FIELD.to_string(), json!(scoring::norm(&vector)));
+            }
+            stamp_updated(&mut metadata);
+            entries.push((record.id, vector, metadata));
+
+            if let Some(progress) = &progress_callback {
+                progress.call1(py, (done + 1, total))?;
+            }
+        }
+
+        let mut store = self.write_store()?;
+        let mut failure = None;
+        for (id, vector, metadata) in entries {
+            if let Err(e) = store.upsert(id, vector, metadata) {
+                failure = Some(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to add vector: {}",
+                    e
+                )));
+                break;
+            }
+        }
+        drop(store);
+
+        // A backend failure leaves the vectors before it transformed, so the
+        // change is checkpointed and reported either way
+        self.notify(py, "replace_all", None)?;
+        match failure {
+            Some(e) => Err(e),
+            None => {
+                info!("Transformed {} vectors", total);
+                Ok(total)
+            }
+        }
+    }
+
+    /// Rebuild the ANN index from scratch
+    ///
+    /// After many inserts, overwrites and deletes the HNSW graph keeps stale
+    /// links and recall/latency degrade. This re-inserts every active record
+    /// into a freshly built index (VecStore's own `optimize` is a no-op for
+    /// the HNSW backend) and swaps it in atomically, like `replace_all`.
+    /// Active records and metadata are unchanged, while soft-deleted records
+    /// are dropped (see `soft_delete`); for stores created with `open()`
+    /// the rebuilt store is saved to disk. Run it after heavy churn, or when
+    /// `evaluate_recall` drops; deleting alone does not require it.
+    /// Fires a "replace_all" change event (see `set_on_change`).
+    ///
+    /// Returns:
+    ///     Seconds taken by the rebuild
+    fn optimize(&mut self, py: Python) -> PyResult<f64> {
+        self.ensure_writable()?;
+        let start = Instant::now();
+
+        let entries: Vec<_> = self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .map(|r| (r.id, r.vector, r.metadata))
+            .collect();
+        let count = entries.len();
+        self.swap_in(entries)?;
+
+        let elapsed = start.elapsed().as_secs_f64();
+        info!("Rebuilt index over {} records in {:.3} s", count, elapsed);
+        self.notify(py, "replace_all", None)?;
+        Ok(elapsed)
+    }
+
+    /// Search for similar vectors with optimized memory usage
+    ///
+    /// Results are automatically sorted by relevance score (highest first).
+    /// Only metadata is returned, vectors are NOT included to save memory.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Number of results to return (default: 5)
+    ///     round_scores: Round scores to this many decimal places, for output
+    ///         that is stable across platforms (default: full precision)
+    ///     include_content: Add a "content" key with the decompressed content
+    ///         (None unless the store was created with store_content="compressed")
+    ///     apply_boost: Multiply each candidate's score by its stored `boost`
+    ///         metadata field (default 1.0, set with `update(id, boost=...)`)
+    ///         and re-rank. `k * 4` candidates are fetched so boosted documents
+    ///         just outside the raw top-k can still make it in. The reported
+    ///         score is the boosted one: `score = similarity * boost`.
+    ///     offset: Number of leading results to skip, for pagination. Fetches
+    ///         `offset + k` candidates and returns ranks `[offset, offset + k)`.
+    ///         Equal scores are ordered by id so consecutive pages are
+    ///         consistent, but the approximate index may return a slightly
+    ///         different candidate set for different `offset + k`, so deep
+    ///         pages can occasionally repeat or miss a borderline result.
+    ///     clamp_k: If True (default), a k larger than the store is reduced to
+    ///         `len()`. If False, such a k raises ValueError.
+    ///     normalize_query: Unit-normalize the query vector (only) before
+    ///         searching, for stores whose vectors were normalized at
+    ///         ingestion. A zero vector is left unchanged.
+    ///     tie_break_field: Metadata field (e.g. "published_at") used to order
+    ///         results whose scores are tied. Records missing the field go
+    ///         last among their ties. Default: ties are ordered by id.
+    ///     tie_break_desc: Order tied results by the field descending
+    ///         (default: True, e.g. newest first)
+    ///     tie_epsilon: Scores within this distance of the first score of a
+    ///         tied run count as tied (default: 0.0, exact ties only)
+    ///     score_as: "similarity" (default, higher = better) or "distance"
+    ///         (smaller = better) for sklearn-style consumers. Distances are
+    ///         `1 - cosine` for Cosine, `-dot` for DotProduct and the L2
+    ///         distance for Euclidean. Ranking, boosting, ties and
+    ///         `tie_epsilon` all work on similarities; only the reported score
+    ///         changes, so the list is in ascending distance order.
+    ///     dedup_field: Metadata field (e.g. "doc_id") whose value identifies
+    ///         the source document of a chunk. Only the best-scoring result
+    ///         per distinct value is kept, before offset and k are applied.
+    ///         `(offset + k) * 4` candidates are fetched, so fewer than k
+    ///         results can come back when one document dominates them.
+    ///         Records missing the field are never merged.
+    ///     include_missing_as_none: Report title, url or summary as None
+    ///         when the record lacks them, instead of an empty string
+    ///     dry_run: Return only the number of results this call would
+    ///         return, without building result dicts. The query still runs;
+    ///         the count reflects the store size, offset, and the candidates
+    ///         removed by dedup_field (search takes no metadata filter; see
+    ///         `find` for filter-only lookups).
+    ///     pinned_ids: Ids promoted to the top of the results, in the given
+    ///         order, with their real scores against the query (boosted with
+    ///         apply_boost, plus the rerank bonus with use_rerank_field). The remaining slots are filled with the normal hits,
+    ///         which never repeat a pinned id. Pinned results count towards k
+    ///         and offset, and are exempt from dedup_field and tie-breaking.
+    ///         Ids not in the store (or soft-deleted) are ignored.
+    ///     stable_insertion_order: Order results with equal scores by when
+    ///         they were last written (oldest first) instead of by id. Every
+    ///         insert and overwrite stamps the record with a sequence number,
+    ///         an internal metadata int costing a few bytes per record plus
+    ///         one scan of the store when it is opened; records written
+    ///         before this existed go last among their ties. tie_break_field,
+    ///         when set, still takes precedence.
+    ///     sort_by: Metadata field (e.g. "published_at" or "title") to order
+    ///         the returned page by. The results are still selected by
+    ///         similarity (including pinned_ids, offset and k); this only
+    ///         reorders them, it never changes which documents come back.
+    ///         Records missing the field go last; equal values keep their
+    ///         relevance order. Numbers, strings and bools compare naturally.
+    ///     sort_desc: Order by sort_by descending (default: False)
+    ///     use_rerank_field: Add each candidate's stored `rerank_score_field`
+    ///         value (e.g. an offline cross-encoder score) to its score and
+    ///         re-rank, after apply_boost: `score = similarity * boost +
+    ///         rerank`. `k * 4` candidates are fetched, as with apply_boost.
+    ///         Records without a numeric value get no bonus. The stored
+    ///         scores should be on a scale comparable to the similarities.
+    ///     rerank_score_field: Metadata field read by use_rerank_field
+    ///         (default: "rerank_score"); use one field per query class,
+    ///         e.g. "rerank_legal", set with `set_metadata_batch`
+    ///     score_transform: Normalization of the returned scores across the
+    ///         page, applied last (after offset and k; rounding applies to the
+    ///         transformed value). "none" (default); "softmax" turns the
+    ///         similarities into weights summing to 1,
+    ///         `exp(s_i / T) / sum_j exp(s_j / T)`; "minmax" rescales them so
+    ///         the best result is 1.0 and the worst 0.0 (all 1.0 when equal).
+    ///         Only valid with score_as="similarity". Transformed scores
+    ///         depend on which results are on the page, so they are not
+    ///         comparable across queries or pages.
+    ///     temperature: Softmax temperature T (default: 1.0). Cosine
+    ///         similarities span at most [-1, 1], so T=1 gives nearly uniform
+    ///         weights; values like 0.05-0.1 sharpen towards the top hits and
+    ///         T -> 0 approaches all weight on the best one. Ignored by the
+    ///         other transforms.
+    ///
+    /// Returns:
+    ///     With dry_run=True, an int. Otherwise a
+    ///     list of dictionaries sorted by relevance with:
+    ///     - id: Document identifier
+    ///     - score: Relevance score (higher = more relevant, or smaller =
+    ///       more relevant with score_as="distance")
+    ///     - title: Document title
+    ///     - url: Document URL
+    ///     - summary: Document summary
+    ///
+    /// Raises:
+    ///     ValueError: If k is 0, exceeds the store size with clamp_k=False,
+    ///         score_as is not "similarity" or "distance", score_transform is
+    ///         unknown or combined with score_as="distance", or temperature
+    ///         is not positive
+    #[pyo3(signature = (
+        vector,
+        k = None,
+        round_scores = None,
+        include_content = false,
+        apply_boost = false,
+        offset = 0,
+        clamp_k = true,
+        normalize_query = false,
+        tie_break_field = None,
+        tie_break_desc = true,
+        tie_epsilon = 0.0,
+        score_as = "similarity",
+        dedup_field = None,
+        include_missing_as_none = false,
+        dry_run = false,
+        pinned_ids = None,
+        stable_insertion_order = false,
+        sort_by = None,
+        sort_desc = false,
+        use_rerank_field = false,
+        rerank_score_field = DEFAULT_RERANK_FIELD,
+        score_transform = "none",
+        temperature = 1.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        py: Python,
+        mut vector: Vec<f32>,
+        k: Option<usize>,
+        round_scores: Option<u32>,
+        include_content: bool,
+        apply_boost: bool,
+        offset: usize,
+        clamp_k: bool,
+        normalize_query: bool,
+        tie_break_field: Option<&str>,
+        tie_break_desc: bool,
+        tie_epsilon: f32,
+        score_as: &str,
+        dedup_field: Option<&str>,
+        include_missing_as_none: bool,
+        dry_run: bool,
+        pinned_ids: Option<Vec<Bound<'_, PyAny>>>,
+        stable_insertion_order: bool,
+        sort_by: Option<&str>,
+        sort_desc: bool,
+        use_rerank_field: bool,
+        rerank_score_field: &str,
+        score_transform: &str,
+        temperature: f32,
+    ) -> PyResult<Py<PyAny>> {
+        let score_kind = ScoreKind::parse(score_as)?;
+        let transform = ScoreTransform::parse(score_transform)?;
+        if transform != ScoreTransform::None && score_kind == ScoreKind::Distance {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "score_transform requires score_as='similarity'",
+            ));
+        }
+        if !(temperature > 0.0 && temperature.is_finite()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "temperature must be positive, got {}",
+                temperature
+            )));
+        }
+        if normalize_query {
+            scoring::normalize(&mut vector);
+        }
+        let pinned = match pinned_ids {
+            Some(ids) => {
+                self.check_dimension(&vector)?;
+                let keys = ids.iter().map(|id| self.key_from_py(id)).collect::<PyResult<Vec<_>>>()?;
+                self.pinned_neighbors(&keys, &vector)?
+            }
+            None => Vec::new(),
+        };
+        let k = self.resolve_k(k)?;
+        let options = result::SearchOptions {
+            k,
+            offset,
+            apply_boost,
+            rerank_field: use_rerank_field.then_some(rerank_score_field),
+            stable_insertion_order,
+            dedup_field,
+            tie_break: tie_break_field.map(|field| (field, tie_break_desc, tie_epsilon)),
+            sort_by: sort_by.map(|field| (field, sort_desc)),
+        };
+        let results = self.run_query(vector, options.fetch())?;
+        // Only a short answer can mean k exceeds the store, so only then count it
+        if !clamp_k && results.len() < k {
+            let len = self.read_store()?.len();
+            if k > len {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "k ({}) exceeds the number of documents ({})",
+                    k, len
+                )));
+            }
+        }
+        let results = options.page(results, pinned);
+        if dry_run {
+            return Ok(results.len().into_pyobject(py)?.into_any().unbind());
+        }
+        let mut scores: Vec<f32> = results.iter().map(|r| r.score).collect();
+        transform.apply(&mut scores, temperature);
+
+        // Convert results to Python list - stream processing for memory efficiency
+        let result_list = PyList::empty(py);
+
+        for (result, score) in results.into_iter().zip(scores) {
+            let score = match score_kind {
+                ScoreKind::Similarity => score,
+                ScoreKind::Distance => scoring::to_distance(self.metric, score),
+            };
+            let dict = self.result_dict(py, &result.id, score, &result.metadata)?;
+            if let Some(places) = round_scores {
+                dict.set_item("score", round_to(score as f64, places))?;
+            }
+            if include_content {
+                dict.set_item("content", decompress_content(&result.metadata)?)?;
+            }
+            if include_missing_as_none {
+                set_missing_to_none(&dict, &result.metadata)?;
+            }
+            result_list.append(dict)?;
+        }
+
+        Ok(result_list.into_any().unbind())
+    }
+
+    /// Search and partition the results into score tiers
+    ///
+    /// Thresholds are given best tier first, in the direction of `score_as`:
+    /// descending similarities (e.g. `[0.8, 0.5]`) or ascending distances
+    /// (e.g. `[0.2, 0.5]`). A result goes into the first tier whose threshold
+    /// it meets (`>=` for similarities, `<=` for distances); results that meet
+    /// none go into the last tier.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     thresholds: Tier boundaries, best tier first
+    ///     k: Number of results to fetch and partition (default: 5)
+    ///     labels: Tier names, one more than thresholds (default: "tier_0",
+    ///         "tier_1", ..., with the last one holding the rest)
+    ///     score_as: "similarity" (default) or "distance", as in `search`
+    ///
+    /// Returns:
+    ///     Dict of tier name to result list (same dicts as `search`), in tier
+    ///     order; every tier is present, empty ones as []
+    ///
+    /// Raises:
+    ///     ValueError: If the thresholds are not strictly ordered best first
+    ///         or the number of labels is not `len(thresholds) + 1`
+    #[pyo3(signature = (vector, thresholds, k = None, labels = None, score_as = "similarity"))]
+    fn search_tiered(
+        &self,
+        py: Python,
+        vector: Vec<f32>,
+        thresholds: Vec<f32>,
+        k: Option<usize>,
+        labels: Option<Vec<String>>,
+        score_as: &str,
+    ) -> PyResult<Py<PyDict>> {
+        let score_kind = ScoreKind::parse(score_as)?;
+        // True if `a` is a strictly better score than `b`
+        let better = |a: f32, b: f32| match score_kind {
+            ScoreKind::Similarity => a > b,
+            ScoreKind::Distance => a < b,
+        };
+        if thresholds.windows(2).any(|w| !better(w[0], w[1])) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "thresholds must be strictly {} for score_as='{}'",
+                if score_kind == ScoreKind::Similarity { "descending" } else { "ascending" },
+                score_as
+            )));
+        }
+        let labels = match labels {
+            Some(labels) if labels.len() != thresholds.len() + 1 => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Expected {} labels for {} thresholds, got {}",
+                    thresholds.len() + 1,
+                    thresholds.len(),
+                    labels.len()
+                )));
+            }
+            Some(labels) => labels,
+            None => (0..=thresholds.len()).map(|i| format!("tier_{}", i)).collect(),
+        };
+
+        let results = self.run_query(vector, self.resolve_k(k)?)?;
+
+        let tiers: Vec<Bound<'_, PyList>> = labels.iter().map(|_| PyList::empty(py)).collect();
+        for result in results {
+            let score = match score_kind {
+                ScoreKind::Similarity => result.score,
+                ScoreKind::Distance => scoring::to_distance(self.metric, result.score),
+            };
+            let tier = thresholds
+                .iter()
+                .position(|&t| !better(t, score))
+                .unwrap_or(thresholds.len());
+            tiers[tier].append(self.result_dict(py, &result.id, score, &result.metadata)?)?;
+        }
+
+        let output = PyDict::new(py);
+        for (label, tier) in labels.into_iter().zip(tiers) {
+            output.set_item(label, tier)?;
+        }
+        Ok(output.into())
+    }
+
+    /// Search with a cutoff at the first sharp score drop (elbow heuristic)
+    ///
+    /// Fetches up to `max_k` results and keeps them until the first one
+    /// whose score falls by more than `drop_ratio` relative to the previous
+    /// hit, i.e. `prev - score > drop_ratio * |prev|`. The top hit is always
+    /// kept, so at least one result comes back from a non-empty store.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     max_k: Maximum number of results (default: 20)
+    ///     drop_ratio: Relative drop that ends the list, e.g. 0.2 for a 20%
+    ///         fall between consecutive hits (default: 0.2)
+    ///
+    /// Returns:
+    ///     List of result dicts (same as `search`), between 1 and `max_k` long
+    ///
+    /// Raises:
+    ///     ValueError: If max_k is 0 or drop_ratio is negative or not finite
+    #[pyo3(signature = (vector, max_k = 20, drop_ratio = 0.2))]
+    fn search_adaptive(&self, py: Python, vector: Vec<f32>, max_k: usize, drop_ratio: f32) -> PyResult<Py<PyList>> {
+        if !(drop_ratio >= 0.0 && drop_ratio.is_finite()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "drop_ratio must be a finite, non-negative number, got {}",
+                drop_ratio
+            )));
+        }
+        let mut results = self.run_query(vector, self.resolve_k(Some(max_k))?)?;
+
+        let cut = results
+            .windows(2)
+            .position(|w| w[0].score - w[1].score > drop_ratio * w[0].score.abs())
+            .map_or(results.len(), |i| i + 1);
+        results.truncate(cut);
+
+        let result_list = PyList::empty(py);
+        for result in results {
+            result_list.append(self.result_dict(py, &result.id, result.score, &result.metadata)?)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Search the sparse vectors attached with `set_sparse`
+    ///
+    /// Scores every active record by the sparse dot product with the query
+    /// (no inverted index, O(n * nnz)); the scan runs without holding the
+    /// GIL. Records without a sparse vector, or sharing no index with the
+    /// query, are never returned, so fewer than k results can come back.
+    ///
+    /// Args:
+    ///     indices: Query dimension indices
+    ///     values: Query weights, one per index
+    ///     k: Number of results to return (default: 5)
+    ///
+    /// Returns:
+    ///     List of result dicts (same as `search`) sorted by descending dot
+    ///     product, ties by id
+    ///
+    /// Raises:
+    ///     ValueError: If the query is malformed (see `set_sparse`) or k is 0
+    #[pyo3(signature = (indices, values, k = 5))]
+    fn search_sparse(slf: PyRef<'_, Self>, indices: Vec<u32>, values: Vec<f32>, k: usize) -> PyResult<Py<PyList>> {
+        if k == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1"));
+        }
+        let query = sparse::SparseVector::parse(indices, values)?;
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+
+        let result_list = PyList::empty(py);
+        for result in store.sparse_neighbors(py, &query, k)? {
+            result_list.append(result_dict(py, store.id_type, &result.id, result.score, &result.metadata)?)?;
+        }
+        Ok(result_list.into())
+    }
+
+    /// Search dense and sparse vectors together and fuse the scores
+    ///
+    /// Fetches `k * 4` candidates from the dense index and from the sparse
+    /// scan (see `search_sparse`), min-max normalizes each list to [0, 1]
+    /// (like `search_ensemble`), and ranks the union by
+    /// `alpha * dense + (1 - alpha) * sparse`. A candidate missing from one
+    /// list contributes 0 for it.
+    ///
+    /// Args:
+    ///     vector: Dense query vector
+    ///     indices: Sparse query dimension indices
+    ///     values: Sparse query weights, one per index
+    ///     k: Number of results to return (default: 5)
+    ///     alpha: Weight of the dense score, between 0 and 1 (default: 0.5)
+    ///
+    /// Returns:
+    ///     List of result dicts (same as `search`) whose score is the fused
+    ///     score, sorted descending, ties by id
+    ///
+    /// Raises:
+    ///     ValueError: If alpha is outside [0, 1], k is 0, the dense vector
+    ///         has the wrong dimension, or the sparse query is malformed
+    #[pyo3(signature = (vector, indices, values, k = 5, alpha = 0.5))]
+    fn search_hybrid_sparse(
+        slf: PyRef<'_, Self>,
+        vector: Vec<f32>,
+        indices: Vec<u32>,
+        values: Vec<f32>,
+        k: usize,
+        alpha: f32,
+    ) -> PyResult<Py<PyList>> {
+        if !(0.0..=1.0).contains(&alpha) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "alpha must be between 0 and 1, got {}",
+                alpha
+            )));
+        }
+        if k == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1"));
+        }
+        let query = sparse::SparseVector::parse(indices, values)?;
+        let fetch = k.saturating_mul(OVERFETCH_FACTOR);
+
+        let dense = slf.run_query(vector, slf.resolve_k(Some(fetch))?)?;
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+        let sparse = store.sparse_neighbors(py, &query, fetch)?;
+
+        let mut combined: HashMap<String, (f32, Metadata)> = HashMap::new();
+        for (results, weight) in [(dense, alpha), (sparse, 1.0 - alpha)] {
+            let mut scores: Vec<f32> = results.iter().map(|r| r.score).collect();
+            ScoreTransform::MinMax.apply(&mut scores, 1.0);
+            for (result, normalized) in results.into_iter().zip(scores) {
+                combined
+                    .entry(result.id)
+                    .or_insert_with(|| (0.0, result.metadata))
+                    .0 += weight * normalized;
+            }
+        }
+
+        let mut ranked: Vec<(String, (f32, Metadata))> = combined.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.0.total_cmp(&a.1.0).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(k);
+
+        let result_list = PyList::empty(py);
+        for (key, (score, metadata)) in ranked {
+            result_list.append(result_dict(py, store.id_type, &key, score, &metadata)?)?;
+        }
+        Ok(result_list.into())
+    }
+
+    /// Search and return the results as a column-oriented dict of lists
+    ///
+    /// The layout can be passed directly to `pandas.DataFrame(...)` and avoids
+    /// building one dict per result row.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Number of results to return (default: 5)
+    ///
+    /// Returns:
+    ///     Dictionary with equal-length lists under "id", "score", "title",
+    ///     "url" and "summary", ordered by relevance
+    #[pyo3(signature = (vector, k = None))]
+    fn search_columns(&self, py: Python, vector: Vec<f32>, k: Option<usize>) -> PyResult<Py<PyDict>> {
+        let results = self.run_query(vector, self.resolve_k(k)?)?;
+
+        let ids = PyList::empty(py);
+        let scores = PyList::empty(py);
+        let titles = PyList::empty(py);
+        let urls = PyList::empty(py);
+        let summaries = PyList::empty(py);
+
+        for result in results {
+            ids.append(self.key_to_py(py, &result.id)?)?;
+            scores.append(result.score)?;
+            titles.append(meta_str(&result.metadata, "title"))?;
+            urls.append(meta_str(&result.metadata, "url"))?;
+            summaries.append(meta_str(&result.metadata, "summary"))?;
+        }
+
+        let columns = PyDict::new(py);
+        columns.set_item("id", ids)?;
+        columns.set_item("score", scores)?;
+        columns.set_item("title", titles)?;
+        columns.set_item("url", urls)?;
+        columns.set_item("summary", summaries)?;
+
+        Ok(columns.into())
+    }
+
+    /// Search with maximal marginal relevance (MMR) re-ranking
+    ///
+    /// Fetches `fetch_k` candidates, then greedily picks `k` of them, each
+    /// time taking the candidate maximizing
+    /// `lambda_mult * sim(query, c) - (1 - lambda_mult) * max(sim(c, s))`
+    /// over the already selected results `s`, using the stored vectors and
+    /// the store metric. This trades some relevance for less redundant
+    /// results.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Number of results to return (default: 5)
+    ///     fetch_k: Number of candidates to choose from (default: 20)
+    ///     lambda_mult: 1.0 ranks purely by relevance, 0.0 purely by
+    ///         diversity (default: 0.5)
+    ///
+    /// Returns:
+    ///     List of result dicts (same shape as `search`) in selection order;
+    ///     "score" is the query similarity, not the MMR objective
+    #[pyo3(signature = (vector, k = None, fetch_k = 20, lambda_mult = 0.5))]
+    fn search_mmr(
+        &self,
+        py: Python,
+        vector: Vec<f32>,
+        k: Option<usize>,
+        fetch_k: usize,
+        lambda_mult: f32,
+    ) -> PyResult<Py<PyList>> {
+        if !(0.0..=1.0).contains(&lambda_mult) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "lambda_mult must be between 0 and 1, got {}",
+                lambda_mult
+            )));
+        }
+        let k = self.resolve_k(k)?;
+        let candidates = self.run_query(vector, fetch_k.max(k))?;
+
+        let mut vectors = self.lookup_vectors(candidates.iter().map(|c| c.id.as_str()))?;
+        let candidates: Vec<(Neighbor, Vec<f32>)> = candidates
+            .into_iter()
+            .filter_map(|c| vectors.remove(&c.id).map(|v| (c, v)))
+            .collect();
+
+        let relevance: Vec<f32> = candidates.iter().map(|(c, _)| c.score).collect();
+        let candidate_vectors: Vec<&[f32]> = candidates.iter().map(|(_, v)| v.as_slice()).collect();
+        let selected = scoring::mmr(self.metric, &relevance, &candidate_vectors, k, lambda_mult);
+
+        let result_list = PyList::empty(py);
+        for i in selected {
+            let (result, _) = &candidates[i];
+            result_list.append(self.result_dict(py, &result.id, result.score, &result.metadata)?)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Search and also return the pairwise scores among the results
+    ///
+    /// Useful for building neighbor graphs: the matrix is computed in Rust
+    /// from the stored vectors of the retrieved results, so no per-pair
+    /// vector fetches are needed on the Python side.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Number of results to return (default: 5)
+    ///
+    /// Returns:
+    ///     Tuple `(results, matrix)`: the result dicts (same shape as
+    ///     `search`) and a float32 array of shape (len(results), len(results))
+    ///     where `matrix[i, j]` is the store-metric similarity between
+    ///     results i and j (higher = more similar, see `similarity`)
+    #[pyo3(signature = (vector, k = None))]
+    fn search_with_neighbor_matrix(&self, py: Python, vector: Vec<f32>, k: Option<usize>) -> PyResult<Py<PyAny>> {
+        let results = self.run_query(vector, self.resolve_k(k)?)?;
+
+        // Rows follow the result order; a result deleted in between is dropped
+        let mut vectors = self.lookup_vectors(results.iter().map(|r| r.id.as_str()))?;
+        let neighbors: Vec<(Neighbor, Vec<f32>)> = results
+            .into_iter()
+            .filter_map(|r| vectors.remove(&r.id).map(|v| (r, v)))
+            .collect();
+
+        let neighbor_vectors: Vec<&[f32]> = neighbors.iter().map(|(_, v)| v.as_slice()).collect();
+        let n = neighbor_vectors.len();
+        let matrix = PyArray1::from_vec(py, scoring::pairwise(self.metric, &neighbor_vectors)).reshape([n, n])?;
+
+        let result_list = PyList::empty(py);
+        for (result, _) in &neighbors {
+            result_list.append(self.result_dict(py, &result.id, result.score, &result.metadata)?)?;
+        }
+
+        Ok((result_list, matrix).into_pyobject(py)?.into_any().unbind())
+    }
+
+    /// Build the k-nearest-neighbor graph over every active record
+    ///
+    /// Each record's stored vector is run as a query against the index and
+    /// the record itself is dropped from its own neighbors. This is one ANN
+    /// query per record, O(n * query), so it is meant for offline clustering
+    /// and visualization rather than hot paths. The GIL is released while
+    /// the queries run.
+    ///
+    /// Args:
+    ///     k: Number of neighbors per record (default: 5); fewer are
+    ///         returned when the store has at most k records
+    ///
+    /// Returns:
+    ///     List of `(id, [(neighbor_id, score), ...])` tuples ordered by id,
+    ///     each neighbor list sorted by score (descending)
+    ///
+    /// Raises:
+    ///     ValueError: If k is 0
+    #[pyo3(signature = (k = 5))]
+    fn knn_graph(slf: PyRef<'_, Self>, k: usize) -> PyResult<Py<PyList>> {
+        if k == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1"));
+        }
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+        let mut records = store.read_store()?.list_active();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        let fetch = k.saturating_add(1).min(records.len());
+
+        let graph = py.detach(|| -> PyResult<Vec<(String, Vec<Neighbor>)>> {
+            records
+                .into_iter()
+                .map(|record| {
+                    let mut neighbors = store.run_query(record.vector, fetch)?;
+                    neighbors.retain(|n| n.id != record.id);
+                    neighbors.truncate(k);
+                    Ok((record.id, neighbors))
+                })
+                .collect()
+        })?;
+
+        let result_list = PyList::empty(py);
+        for (key, neighbors) in graph {
+            let edges = PyList::empty(py);
+            for neighbor in neighbors {
+                edges.append((store.id_type.key_to_py(py, &neighbor.id)?, neighbor.score))?;
+            }
+            result_list.append((store.id_type.key_to_py(py, &key)?, edges))?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Distance from every record to its kth nearest neighbor
+    ///
+    /// A large kth-NN distance marks a record in a sparse region, the usual
+    /// local-density signal for outlier detection. Like `knn_graph`, this
+    /// runs one ANN query per record (O(n * query)) with the GIL released,
+    /// and the record itself is not counted as a neighbor.
+    ///
+    /// Args:
+    ///     k: Which neighbor to measure to (default: 5, 1 = nearest)
+    ///
+    /// Returns:
+    ///     Dict of id to the distance to its kth neighbor, on the same scale
+    ///     as `search(score_as="distance")`; None for records with fewer
+    ///     than k other records available
+    ///
+    /// Raises:
+    ///     ValueError: If k is 0
+    #[pyo3(signature = (k = 5))]
+    fn knn_distances(slf: PyRef<'_, Self>, k: usize) -> PyResult<Py<PyDict>> {
+        if k == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1"));
+        }
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+        let mut records = store.read_store()?.list_active();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        let fetch = k.saturating_add(1).min(records.len());
+
+        let distances = py.detach(|| -> PyResult<Vec<(String, Option<f32>)>> {
+            records
+                .into_iter()
+                .map(|record| {
+                    let kth = store
+                        .run_query(record.vector, fetch)?
+                        .into_iter()
+                        .filter(|n| n.id != record.id)
+                        .nth(k - 1)
+                        .map(|n| scoring::to_distance(store.metric, n.score));
+                    Ok((record.id, kth))
+                })
+                .collect()
+        })?;
+
+        let result = PyDict::new(py);
+        for (key, distance) in distances {
+            result.set_item(store.id_type.key_to_py(py, &key)?, distance)?;
+        }
+
+        Ok(result.into())
+    }
+
+    /// Return the k documents closest to the store's centroid
+    ///
+    /// The centroid is the mean of all active vectors; it is then run as a
+    /// single query, so the results are the most "central", representative
+    /// documents under the store metric. For Cosine stores of normalized
+    /// embeddings this is the mean direction.
+    ///
+    /// Args:
+    ///     k: Number of documents to return (default: 5)
+    ///
+    /// Returns:
+    ///     List of result dicts (same shape as `search`) sorted by their
+    ///     score against the centroid (descending)
+    ///
+    /// Raises:
+    ///     ValueError: If the store is empty or k is 0
+    #[pyo3(signature = (k = None))]
+    fn medoids(&self, py: Python, k: Option<usize>) -> PyResult<Py<PyList>> {
+        let k = self.resolve_k(k)?;
+        let centroid = scoring::centroid(&self.read_store()?.list_active()).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>("Cannot compute medoids of an empty store")
+        })?;
+
+        let result_list = PyList::empty(py);
+        for result in self.run_query(centroid, k)? {
+            result_list.append(self.result_dict(py, &result.id, result.score, &result.metadata)?)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Audit the store for groups of (near-)duplicate vectors
+    ///
+    /// Each record's `neighbors` nearest neighbors are fetched from the ANN
+    /// index (one query per record instead of an O(n^2) pairwise scan) and
+    /// pairs scoring at least `threshold` are linked. Linked records are
+    /// merged transitively, so a group can contain two records below the
+    /// threshold that are both close to a third. Duplicates beyond the
+    /// `neighbors` nearest of every member can be missed. The GIL is
+    /// released while the queries run.
+    ///
+    /// Args:
+    ///     threshold: Minimum similarity for two records to count as
+    ///         duplicates, on the `search` score scale (e.g. 0.99 for Cosine)
+    ///     neighbors: Candidates checked per record (default: 10)
+    ///
+    /// Returns:
+    ///     List of groups (lists of at least two ids), each sorted by id,
+    ///     ordered by their first id
+    ///
+    /// Raises:
+    ///     ValueError: If neighbors is 0
+    #[pyo3(signature = (threshold, neighbors = 10))]
+    fn find_duplicates(slf: PyRef<'_, Self>, threshold: f32, neighbors: usize) -> PyResult<Py<PyList>> {
+        if neighbors == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "neighbors must be at least 1",
+            ));
+        }
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+        let mut records = store.read_store()?.list_active();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        let ids: Vec<String> = records.iter().map(|r| r.id.clone()).collect();
+        let index: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+        let fetch = neighbors.saturating_add(1).min(records.len());
+
+        // Union-find over record indices, linking every pair above the threshold
+        let mut parent: Vec<usize> = (0..ids.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        py.detach(|| -> PyResult<()> {
+            for (i, record) in records.into_iter().enumerate() {
+                for neighbor in store.run_query(record.vector, fetch)? {
+                    if neighbor.score < threshold {
+                        break;
+                    }
+                    if let Some(&j) = index.get(neighbor.id.as_str()) {
+                        let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                        parent[a.max(b)] = a.min(b);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for i in 0..ids.len() {
+            groups.entry(root(&mut parent, i)).or_default().push(i);
+        }
+        let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+        groups.sort_unstable_by_key(|g| g[0]);
+
+        let result_list = PyList::empty(py);
+        for group in groups {
+            let members = PyList::empty(py);
+            for i in group {
+                members.append(store.id_type.key_to_py(py, &ids[i])?)?;
+            }
+            result_list.append(members)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Open a cursor over every record in descending similarity to a vector
+    ///
+    /// For infinite-scroll style paging through the whole store by relevance.
+    /// Unlike `search(offset=...)` the order is exact (brute force) and
+    /// pages never repeat or skip a record, even when records are written
+    /// between calls. See `RankedCursor.next_batch`.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///
+    /// Returns:
+    ///     RankedCursor
+    ///
+    /// Raises:
+    ///     ValueError: If the vector has the wrong dimension
+    fn ranked_cursor(slf: Bound<'_, Self>, vector: Vec<f32>) -> PyResult<result::RankedCursor> {
+        slf.borrow().check_dimension(&vector)?;
+        Ok(result::RankedCursor::new(slf.unbind(), vector))
+    }
+
+    /// Search and yield the results lazily, for very large k
+    ///
+    /// The ranked candidates are fetched in one query under the read lock,
+    /// which is released before the first result is yielded, so writers are
+    /// never blocked while the consumer iterates. Only ids, scores and
+    /// metadata of the candidates are kept; each result dict is built on
+    /// demand, so stopping early skips the conversion cost of the rest.
+    /// Writes made during iteration are not reflected in the results.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Maximum number of results, clamped to the store size (default: 5)
+    ///
+    /// Returns:
+    ///     Iterator of result dicts (same shape as `search`) sorted by score
+    ///     (descending); `len()` gives the number of results left
+    #[pyo3(signature = (vector, k = None))]
+    fn search_iter(slf: Bound<'_, Self>, vector: Vec<f32>, k: Option<usize>) -> PyResult<result::SearchIter> {
+        let results = {
+            let store = slf.borrow();
+            store.run_query(vector, store.resolve_k(k)?)?
+        };
+        Ok(result::SearchIter::new(slf.unbind(), results))
+    }
+
+    /// Search and return typed `SearchResult` objects instead of dicts
+    ///
+    /// Same ranking as `search`; attributes give IDE completion and raise
+    /// AttributeError on typos instead of silently missing dict keys.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Number of results to return (default: 5)
+    ///     include_vectors: Fill in `SearchResult.vector` (default: False)
+    ///
+    /// Returns:
+    ///     List of SearchResult sorted by score (descending)
+    #[pyo3(signature = (vector, k = None, include_vectors = false))]
+    fn search_objects(
+        &self,
+        py: Python,
+        vector: Vec<f32>,
+        k: Option<usize>,
+        include_vectors: bool,
+    ) -> PyResult<Vec<result::SearchResult>> {
+        let results = self.run_query(vector, self.resolve_k(k)?)?;
+
+        // Neighbors carry no vectors; look them up only when asked
+        let mut vectors = if include_vectors {
+            self.lookup_vectors(results.iter().map(|r| r.id.as_str()))?
+        } else {
+            HashMap::new()
+        };
+
+        results
+            .into_iter()
+            .map(|r| {
+                Ok(result::SearchResult {
+                    id: self.key_to_py(py, &r.id)?,
+                    score: r.score,
+                    title: meta_str(&r.metadata, "title").to_string(),
+                    url: meta_str(&r.metadata, "url").to_string(),
+                    summary: meta_str(&r.metadata, "summary").to_string(),
+                    vector: vectors.remove(&r.id),
+                })
+            })
+            .collect()
+    }
+
+    /// Search and return the results as a JSON string
+    ///
+    /// Builds the JSON directly with serde_json, skipping the Python dict and
+    /// list objects entirely, so the string can be handed to an HTTP response
+    /// as-is. Each result has the same fields as `search`. Ids are JSON
+    /// numbers for `id_type="int"` stores and strings otherwise. Scores are
+    /// written in the shortest form that round-trips the f32 value
+    /// (e.g. `0.9`, not `0.8999999761581421`).
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Number of results to return (default: 5)
+    ///
+    /// Returns:
+    ///     JSON array of result objects, ordered by relevance
+    #[pyo3(signature = (vector, k = None))]
+    fn search_json(&self, vector: Vec<f32>, k: Option<usize>) -> PyResult<String> {
+        #[derive(serde::Serialize)]
+        struct JsonResult<'a> {
+            id: serde_json::Value,
+            score: f32,
+            title: &'a str,
+            url: &'a str,
+            summary: &'a str,
+        }
+
+        let results = self.run_query(vector, self.resolve_k(k)?)?;
+        let rows: Vec<JsonResult> = results
+            .iter()
+            .map(|result| JsonResult {
+                id: match self.id_type {
+                    IdType::Int => result.id.parse::<i64>().map_or_else(|_| json!(result.id), |n| json!(n)),
+                    IdType::Str => json!(result.id),
+                },
+                score: result.score,
+                title: meta_str(&result.metadata, "title"),
+                url: meta_str(&result.metadata, "url"),
+                summary: meta_str(&result.metadata, "summary"),
+            })
+            .collect();
+
+        serde_json::to_string(&rows).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                "Failed to serialize results: {}",
+                e
+            ))
+        })
+    }
+
+    /// Find the documents most similar to a stored document
+    ///
+    /// The document's own vector is the query and the document itself is
+    /// left out of the results. With `exclude_same=True`, results sharing
+    /// the source's value of `exclude_field` (e.g. the same "source" site)
+    /// are also dropped, for "related, but from elsewhere" recommendations;
+    /// `k * 4` candidates are then fetched so k results usually remain. If
+    /// the source has no value for the field, nothing extra is excluded.
+    ///
+    /// Args:
+    ///     id: Identifier of the source document
+    ///     k: Number of results to return (default: 5)
+    ///     exclude_field: Metadata field compared against the source
+    ///     exclude_same: Drop results whose `exclude_field` equals the source's
+    ///         (default: False)
+    ///
+    /// Returns:
+    ///     List of result dicts (same shape as `search`) sorted by score
+    ///     (descending); may hold fewer than k results
+    ///
+    /// Raises:
+    ///     KeyError: If the document is not in the store
+    ///     ValueError: If exclude_same is True without an exclude_field
+    #[pyo3(signature = (id, k = None, exclude_field = None, exclude_same = false))]
+    fn search_by_id(
+        &self,
+        py: Python,
+        id: &Bound<'_, PyAny>,
+        k: Option<usize>,
+        exclude_field: Option<&str>,
+        exclude_same: bool,
+    ) -> PyResult<Py<PyList>> {
+        if exclude_same && exclude_field.is_none() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "exclude_same requires exclude_field",
+            ));
+        }
+        let key = self.key_from_py(id)?;
+        let k = self.resolve_k(k)?;
+
+        let source = self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .find(|r| r.id == key)
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Document not found: {}", key)))?;
+        let excluded = exclude_field
+            .filter(|_| exclude_same)
+            .and_then(|field| source.metadata.fields.get(field).map(|value| (field, value)));
+
+        let fetch = if excluded.is_some() {
+            k.saturating_add(1).saturating_mul(OVERFETCH_FACTOR)
+        } else {
+            k.saturating_add(1)
+        };
+        let mut results = self.run_query(source.vector, fetch)?;
+        results.retain(|r| {
+            r.id != key && excluded.is_none_or(|(field, value)| r.metadata.fields.get(field) != Some(value))
+        });
+        results.truncate(k);
+
+        let result_list = PyList::empty(py);
+        for result in results {
+            result_list.append(self.result_dict(py, &result.id, result.score, &result.metadata)?)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Compute the similarity between two stored documents
+    ///
+    /// Uses the store metric, on the same scale as `search` scores.
+    ///
+    /// Args:
+    ///     id_a: First document identifier
+    ///     id_b: Second document identifier
+    ///
+    /// Returns:
+    ///     Similarity score (higher = more similar)
+    ///
+    /// Raises:
+    ///     KeyError: If either id is not in the store
+    fn similarity(&self, id_a: &Bound<'_, PyAny>, id_b: &Bound<'_, PyAny>) -> PyResult<f32> {
+        let key_a = self.key_from_py(id_a)?;
+        let key_b = self.key_from_py(id_b)?;
+
+        let mut vector_a = None;
+        let mut vector_b = None;
+        for record in self.read_store()?.list_active() {
+            if record.id == key_a {
+                vector_a = Some(record.vector.clone());
+            }
+            if record.id == key_b {
+                vector_b = Some(record.vector);
+            }
+        }
+
+        match (vector_a, vector_b) {
+            (Some(a), Some(b)) => Ok(scoring::similarity(self.metric, &a, &b)),
+            (None, _) => Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Document not found: {}", key_a))),
+            (_, None) => Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Document not found: {}", key_b))),
+        }
+    }
+
+    /// Score every document of one id set against every document of another
+    ///
+    /// Each set's vectors are fetched once and the scores are computed in
+    /// parallel without holding the GIL.
+    ///
+    /// Args:
+    ///     ids_a: Row document identifiers
+    ///     ids_b: Column document identifiers
+    ///     skip_missing: If True, ids that are not in the store are left out
+    ///         and a mask per set is returned alongside the matrix
+    ///
+    /// Returns:
+    ///     float32 array of shape (len(ids_a), len(ids_b)) with the same
+    ///     scores as `similarity(a, b)`. With skip_missing=True, a tuple
+    ///     `(matrix, mask_a, mask_b)` where the masks tell which ids were
+    ///     found (one row or column per True), as in `get_vectors`.
+    ///
+    /// Raises:
+    ///     KeyError: If an id is not in the store and skip_missing is False
+    #[pyo3(signature = (ids_a, ids_b, skip_missing = false))]
+    fn similarity_matrix(
+        slf: PyRef<'_, Self>,
+        ids_a: Vec<Bound<'_, PyAny>>,
+        ids_b: Vec<Bound<'_, PyAny>>,
+        skip_missing: bool,
+    ) -> PyResult<Py<PyAny>> {
+        let py = slf.py();
+        let keys_a = ids_a.iter().map(|id| slf.key_from_py(id)).collect::<PyResult<Vec<_>>>()?;
+        let keys_b = ids_b.iter().map(|id| slf.key_from_py(id)).collect::<PyResult<Vec<_>>>()?;
+        let vectors = slf.lookup_vectors(keys_a.iter().chain(&keys_b).map(String::as_str))?;
+        let store = slf.detached()?;
+        drop(slf);
+
+        let select = |keys: &[String]| -> PyResult<(Vec<&[f32]>, Vec<bool>)> {
+            let mut found = Vec::with_capacity(keys.len());
+            let mut mask = Vec::with_capacity(keys.len());
+            for key in keys {
+                match vectors.get(key) {
+                    Some(vector) => {
+                        found.push(vector.as_slice());
+                        mask.push(true);
+                    }
+                    None if skip_missing => mask.push(false),
+                    None => {
+                        return Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!(
+                            "Document not found: {}",
+                            key
+                        )));
+                    }
+                }
+            }
+            Ok((found, mask))
+        };
+        let (rows, mask_a) = select(&keys_a)?;
+        let (cols, mask_b) = select(&keys_b)?;
+
+        let scores = py.detach(|| store.install(|| scoring::cross(store.metric, &rows, &cols)));
+        let matrix = PyArray1::from_vec(py, scores).reshape([rows.len(), cols.len()])?;
+
+        if skip_missing {
+            Ok((matrix, mask_a, mask_b).into_pyobject(py)?.into_any().unbind())
+        } else {
+            Ok(matrix.into_any().unbind())
+        }
+    }
+
+    /// Fetch the vectors of several documents as one 2D numpy array
+    ///
+    /// Args:
+    ///     ids: Document identifiers
+    ///     skip_missing: If True, ids that are not in the store are left out
+    ///         of the array and a parallel mask is returned alongside it
+    ///
+    /// Returns:
+    ///     float32 array of shape (len(ids), dimension), rows in the order of
+    ///     `ids`. With skip_missing=True, a tuple `(array, mask)` where
+    ///     `mask[i]` tells whether `ids[i]` was found (one row per True).
+    ///
+    /// Raises:
+    ///     KeyError: If an id is not in the store and skip_missing is False
+    #[pyo3(signature = (ids, skip_missing = false))]
+    fn get_vectors(&self, py: Python, ids: Vec<Bound<'_, PyAny>>, skip_missing: bool) -> PyResult<Py<PyAny>> {
+        let keys = ids
+            .iter()
+            .map(|id| self.key_from_py(id))
+            .collect::<PyResult<Vec<_>>>()?;
+        let vectors = self.lookup_vectors(keys.iter().map(String::as_str))?;
+
+        let mut flat = Vec::with_capacity(keys.len() * self.dimension);
+        let mut mask = Vec::with_capacity(keys.len());
+        for key in &keys {
+            match vectors.get(key) {
+                Some(vector) => {
+                    flat.extend_from_slice(vector);
+                    mask.push(true);
+                }
+                None if skip_missing => mask.push(false),
+                None => {
+                    return Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!(
+                        "Document not found: {}",
+                        key
+                    )));
+                }
+            }
+        }
+
+        let rows = flat.len() / self.dimension.max(1);
+        let array = PyArray1::from_vec(py, flat).reshape([rows, self.dimension])?;
+
+        if skip_missing {
+            Ok((array, mask).into_pyobject(py)?.into_any().unbind())
+        } else {
+            Ok(array.into_any().unbind())
+        }
+    }
+
+    /// Fetch the vectors of several documents into a caller-provided array
+    ///
+    /// Like `get_vectors`, but writes into `out` instead of allocating a new
+    /// array per call, for hot retrieval loops that reuse one buffer. The
+    /// lookup itself still scans the active records (VecStore 1.0 has no
+    /// get-by-id), so this saves the result allocation, not the scan.
+    ///
+    /// Args:
+    ///     ids: Document identifiers
+    ///     out: Writeable float32 numpy array of shape (len(ids), dimension).
+    ///         Row i receives the vector of `ids[i]`, or zeros if it was not
+    ///         found.
+    ///
+    /// Returns:
+    ///     List of bools, `mask[i]` telling whether `ids[i]` was found
+    ///
+    /// Raises:
+    ///     TypeError: If `out` is not a writeable 2D float32 array
+    ///     ValueError: If `out` has the wrong shape
+    fn get_vectors_into(
+        &self,
+        ids: Vec<Bound<'_, PyAny>>,
+        mut out: PyReadwriteArray2<'_, f32>,
+    ) -> PyResult<Vec<bool>> {
+        let shape = out.shape();
+        if shape != [ids.len(), self.dimension] {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Output array shape mismatch. Expected ({}, {}), got ({}, {})",
+                ids.len(),
+                self.dimension,
+                shape[0],
+                shape[1]
+            )));
+        }
+
+        let keys = ids
+            .iter()
+            .map(|id| self.key_from_py(id))
+            .collect::<PyResult<Vec<_>>>()?;
+        let vectors = self.lookup_vectors(keys.iter().map(String::as_str))?;
+
+        let mut out = out.as_array_mut();
+        let mut mask = Vec::with_capacity(keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            let mut row = out.row_mut(i);
+            match vectors.get(key) {
+                Some(vector) => {
+                    row.iter_mut().zip(vector).for_each(|(o, &x)| *o = x);
+                    mask.push(true);
+                }
+                None => {
+                    row.fill(0.0);
+                    mask.push(false);
+                }
+            }
+        }
+        Ok(mask)
+    }
+
+    /// Search with several weighted query vectors (query expansion)
+    ///
+    /// Each sub-query is run with an overfetch of `k * 4` candidates. A
+    /// candidate's combined score is the weighted sum of its scores across
+    /// the sub-queries, where a sub-query that did not return the candidate
+    /// contributes 0. The top-k combined scores are returned.
+    ///
+    /// Args:
+    ///     vectors: Query vectors
+    ///     weights: One weight per query vector
+    ///     k: Number of results to return (default: 5)
+    ///
+    /// Returns:
+    ///     List of result dicts (same shape as `search`) sorted by combined score
+    ///     (descending), ties by id
+    #[pyo3(signature = (vectors, weights, k = None))]
+    fn search_weighted(
+        &self,
+        py: Python,
+        vectors: Vec<Vec<f32>>,
+        weights: Vec<f32>,
+        k: Option<usize>,
+    ) -> PyResult<Py<PyList>> {
+        if vectors.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "vectors must not be empty",
+            ));
+        }
+        if vectors.len() != weights.len() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Got {} vectors but {} weights",
+                vectors.len(),
+                weights.len()
+            )));
+        }
+        for vector in &vectors {
+            self.check_dimension(vector)?;
+        }
+
+        let k = self.resolve_k(k)?;
+        let fetch = k.saturating_mul(OVERFETCH_FACTOR);
+
+        let mut combined: HashMap<String, (f32, Metadata)> = HashMap::new();
+        for (vector, weight) in vectors.into_iter().zip(weights) {
+            for result in self.run_query(vector, fetch)? {
+                combined
+                    .entry(result.id)
+                    .or_insert_with(|| (0.0, result.metadata))
+                    .0 += weight * result.score;
+            }
+        }
+
+        let mut ranked: Vec<(String, (f32, Metadata))> = combined.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.0.total_cmp(&a.1.0).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(k);
+
+        let result_list = PyList::empty(py);
+        for (key, (score, metadata)) in ranked {
+            result_list.append(self.result_dict(py, &key, score, &metadata)?)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Estimate ANN recall@k against brute-force ground truth
+    ///
+    /// For each query the exact top-k is computed by scoring every active
+    /// record, and compared with the ids returned by `search`. Useful when
+    /// tuning index or metric settings. Cost is O(queries * n).
+    ///
+    /// Args:
+    ///     query_vectors: List of query vectors
+    ///     k: Number of neighbors to compare per query
+    ///
+    /// Returns:
+    ///     Mean recall@k over all queries (0.0 - 1.0)
+    fn evaluate_recall(&self, query_vectors: Vec<Vec<f32>>, k: usize) -> PyResult<f64> {
+        if query_vectors.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "query_vectors must not be empty",
+            ));
+        }
+        if k == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1"));
+        }
+        for vector in &query_vectors {
+            self.check_dimension(vector)?;
+        }
+
+        // Snapshot records and release the lock before running ANN queries
+        let records = self.read_store()?.list_active();
+        if records.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "Cannot evaluate recall on an empty store",
+            ));
+        }
+
+        // Exact ground truth is the expensive part; compute it in parallel
+        let metric = self.metric;
+        let truths: Vec<HashSet<&str>> = self.install(|| {
+            query_vectors
+                .par_iter()
+                .map(|vector| {
+                    scoring::top_k(metric, &records, vector, k)
+                        .into_iter()
+                        .map(|(r, _)| r.id.as_str())
+                        .collect()
+                })
+                .collect()
+        });
+
+        let mut total = 0.0;
+        for (vector, truth) in query_vectors.iter().zip(&truths) {
+            let found = self
+                .run_query(vector.clone(), k)?
+                .iter()
+                .filter(|n| truth.contains(n.id.as_str()))
+                .count();
+            total += found as f64 / truth.len() as f64;
+        }
+
+        Ok(total / query_vectors.len() as f64)
+    }
+
+    /// Measure how well the store covers a set of queries
+    ///
+    /// For each query the exact nearest stored record is found by scoring
+    /// every active record, and its distance (as reported by
+    /// `search(score_as="distance")`) is compared with `radius`. Useful in
+    /// RAG evaluation to spot query regions the corpus does not cover. Cost
+    /// is O(queries * n); the scan runs without holding the GIL.
+    ///
+    /// Args:
+    ///     query_vectors: List of query vectors
+    ///     radius: Maximum nearest-neighbor distance for a query to count
+    ///         as covered
+    ///
+    /// Returns:
+    ///     Dict with "covered_fraction" (share of queries with a stored
+    ///     record within radius), "mean_nn_distance" and "queries"
+    ///
+    /// Raises:
+    ///     ValueError: If query_vectors is empty, a vector has the wrong
+    ///         dimension, or the store is empty
+    fn coverage(slf: PyRef<'_, Self>, query_vectors: Vec<Vec<f32>>, radius: f32) -> PyResult<Py<PyDict>> {
+        if query_vectors.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "query_vectors must not be empty",
+            ));
+        }
+        for vector in &query_vectors {
+            slf.check_dimension(vector)?;
+        }
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+
+        let records = store.read_store()?.list_active();
+        if records.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "Cannot compute coverage on an empty store",
+            ));
+        }
+
+        let metric = store.metric;
+        let nn_distances: Vec<f32> = py.detach(|| {
+            store.install(|| {
+                query_vectors
+                    .par_iter()
+                    .map(|vector| {
+                        let best = scoring::score_all(metric, &records, vector)
+                            .into_iter()
+                            .fold(f32::NEG_INFINITY, f32::max);
+                        scoring::to_distance(metric, best)
+                    })
+                    .collect()
+            })
+        });
+
+        let queries = nn_distances.len();
+        let covered = nn_distances.iter().filter(|&&d| d <= radius).count();
+        let mean = nn_distances.iter().map(|&d| d as f64).sum::<f64>() / queries as f64;
+
+        let stats = PyDict::new(py);
+        stats.set_item("covered_fraction", covered as f64 / queries as f64)?;
+        stats.set_item("mean_nn_distance", mean)?;
+        stats.set_item("queries", queries)?;
+        Ok(stats.into())
+    }
+
+    /// Summarize the principal components of the stored vectors
+    ///
+    /// Finds the top principal components of the active records by power
+    /// iteration with deflation, without forming the covariance matrix or
+    /// exporting the vectors. Cost is O(n_components * iterations * n * d);
+    /// the computation runs without holding the GIL. Results are
+    /// approximate (converged to about 1e-9 relative) and match sklearn's
+    /// `PCA(...).explained_variance_ratio_` for well-separated components.
+    ///
+    /// Args:
+    ///     n_components: Number of components to compute (default: 10).
+    ///         Fewer are returned when the vectors span fewer dimensions.
+    ///
+    /// Returns:
+    ///     Dict with "explained_variance_ratio" (share of the total variance
+    ///     per component, descending), "explained_variance" (variance along
+    ///     each component) and "total_variance"
+    ///
+    /// Raises:
+    ///     ValueError: If n_components is 0 or the store is empty
+    #[pyo3(signature = (n_components = 10))]
+    fn pca_summary(slf: PyRef<'_, Self>, n_components: usize) -> PyResult<Py<PyDict>> {
+        if n_components == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "n_components must be at least 1",
+            ));
+        }
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+        let records = store.read_store()?.list_active();
+        if records.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "Cannot compute principal components of an empty store",
+            ));
+        }
+
+        let (variances, total) = py.detach(|| store.install(|| pca::principal_variances(&records, n_components)));
+        let ratios: Vec<f64> = variances
+            .iter()
+            .map(|&v| if total > 0.0 { v / total } else { 0.0 })
+            .collect();
+
+        let summary = PyDict::new(py);
+        summary.set_item("explained_variance_ratio", ratios)?;
+        summary.set_item("explained_variance", variances)?;
+        summary.set_item("total_variance", total)?;
+        Ok(summary.into())
+    }
+
+    /// Compare the active records of this store with another store
+    ///
+    /// For validating a rebuilt store against the one it replaces, e.g. in
+    /// CI after a re-embed. Only ids and vectors are compared; metadata is
+    /// ignored. Both stores are scanned in full (O(n) memory for the ids).
+    ///
+    /// Args:
+    ///     other: Store to compare with, same id_type
+    ///     tolerance: Largest per-component absolute difference for two
+    ///         vectors to count as unchanged (default: 1e-6). Vectors of
+    ///         different lengths always count as changed.
+    ///
+    /// Returns:
+    ///     Dict of sets of ids: "only_in_self", "only_in_other" and
+    ///     "changed" (in both, with vectors differing beyond tolerance)
+    ///
+    /// Raises:
+    ///     ValueError: If the id types differ or tolerance is negative
+    #[pyo3(signature = (other, tolerance = 1e-6))]
+    fn diff(&self, py: Python, other: PyRef<'_, VectorStore>, tolerance: f32) -> PyResult<Py<PyDict>> {
+        if self.id_type != other.id_type {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "Cannot diff stores with different id_type",
+            ));
+        }
+        if tolerance.is_nan() || tolerance < 0.0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "tolerance must be non-negative, got {}",
+                tolerance
+            )));
+        }
+
+        let ours: HashMap<String, Vec<f32>> = self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .map(|r| (r.id, r.vector))
+            .collect();
+        let theirs: HashMap<String, Vec<f32>> = other
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .map(|r| (r.id, r.vector))
+            .collect();
+
+        let only_in_self = PySet::empty(py)?;
+        let changed = PySet::empty(py)?;
+        for (key, vector) in &ours {
+            match theirs.get(key) {
+                None => only_in_self.add(self.key_to_py(py, key)?)?,
+                Some(theirs) => {
+                    let moved = vector.len() != theirs.len()
+                        || vector
+                            .iter()
+                            .zip(theirs)
+                            .any(|(a, b)| (a - b).abs() > tolerance || a.is_nan() != b.is_nan());
+                    if moved {
+                        changed.add(self.key_to_py(py, key)?)?;
+                    }
+                }
+            }
+        }
+        let only_in_other = PySet::empty(py)?;
+        for key in theirs.keys().filter(|key| !ours.contains_key(*key)) {
+            only_in_other.add(self.key_to_py(py, key)?)?;
+        }
+
+        let report = PyDict::new(py);
+        report.set_item("only_in_self", only_in_self)?;
+        report.set_item("only_in_other", only_in_other)?;
+        report.set_item("changed", changed)?;
+        Ok(report.into())
+    }
+
+    /// Compute the distribution of scores of every record against a query
+    ///
+    /// Scores all active records exactly with the store metric (O(n) in the
+    /// store size, on the same scale as `search`), which helps choose a
+    /// `min_score`-style relevance cutoff empirically. Bins are equal-width
+    /// between the lowest and highest score; the last bin includes its upper
+    /// edge.
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     bins: Number of bins (default: 10)
+    ///
+    /// Returns:
+    ///     Dictionary with "edges" (bins + 1 floats) and "counts" (bins ints);
+    ///     both lists are empty for an empty store
+    #[pyo3(signature = (vector, bins = 10))]
+    fn score_histogram(&self, py: Python, vector: Vec<f32>, bins: usize) -> PyResult<Py<PyDict>> {
+        if bins == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("bins must be at least 1"));
+        }
+        self.check_dimension(&vector)?;
+
+        let records = self.read_store()?.list_active();
+        let metric = self.metric;
+        let scores = self.install(|| scoring::score_all(metric, &records, &vector));
+
+        let mut edges = Vec::new();
+        let mut counts = Vec::new();
+        if !scores.is_empty() {
+            let lo = scores.iter().copied().fold(f32::INFINITY, f32::min) as f64;
+            let hi = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+            let width = if hi > lo { (hi - lo) / bins as f64 } else { 1.0 };
+
+            edges = (0..=bins).map(|i| lo + width * i as f64).collect();
+            counts = vec![0usize; bins];
+            for score in scores {
+                let bin = ((score as f64 - lo) / width) as usize;
+                counts[bin.min(bins - 1)] += 1;
+            }
+        }
+
+        let histogram = PyDict::new(py);
+        histogram.set_item("edges", edges)?;
+        histogram.set_item("counts", counts)?;
+        Ok(histogram.into())
+    }
+
+    /// Time a set of queries and report latency percentiles and throughput
+    ///
+    /// Every query is run once through the same path as `search`. The GIL is
+    /// released for the timed section, so other Python threads keep running
+    /// and the numbers reflect the store itself.
+    ///
+    /// Args:
+    ///     query_vectors: Query vectors to run
+    ///     k: Number of results per query (default: 5)
+    ///
+    /// Returns:
+    ///     Dictionary with "queries", "p50_ms", "p95_ms", "p99_ms",
+    ///     "mean_ms", "max_ms" and "qps" (queries per second over the whole run)
+    #[pyo3(signature = (query_vectors, k = 5))]
+    fn benchmark(slf: PyRef<'_, Self>, query_vectors: Vec<Vec<f32>>, k: usize) -> PyResult<Py<PyDict>> {
+        if query_vectors.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "query_vectors must not be empty",
+            ));
+        }
+        for vector in &query_vectors {
+            slf.check_dimension(vector)?;
+        }
+        let k = slf.resolve_k(Some(k))?;
+        let queries = query_vectors.len();
+        let py = slf.py();
+        let store = slf.detached()?;
+        drop(slf);
+
+        let (mut latencies, total) = py.detach(|| -> PyResult<_> {
+            let mut latencies = Vec::with_capacity(queries);
+            let start = Instant::now();
+            for vector in query_vectors {
+                let query_start = Instant::now();
+                store.run_query(vector, k)?;
+                latencies.push(query_start.elapsed().as_secs_f64() * 1000.0);
+            }
+            Ok((latencies, start.elapsed().as_secs_f64()))
+        })?;
+        latencies.sort_by(f64::total_cmp);
+
+        // Nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * queries as f64).ceil() as usize;
+            latencies[rank.clamp(1, queries) - 1]
+        };
+
+        let stats = PyDict::new(py);
+        stats.set_item("queries", queries)?;
+        stats.set_item("p50_ms", percentile(50.0))?;
+        stats.set_item("p95_ms", percentile(95.0))?;
+        stats.set_item("p99_ms", percentile(99.0))?;
+        stats.set_item("mean_ms", latencies.iter().sum::<f64>() / queries as f64)?;
+        stats.set_item("max_ms", latencies[queries - 1])?;
+        stats.set_item("qps", if total > 0.0 { queries as f64 / total } else { f64::INFINITY })?;
+
+        Ok(stats.into())
+    }
+
+    /// Return every active record as a list of dicts
+    ///
+    /// Each dict contains the id and all stored metadata fields, plus the
+    /// vector when requested. Intended for small stores and tests; the whole
+    /// store is materialized in memory.
+    ///
+    /// Args:
+    ///     include_vectors: Include each record's vector under "vector"
+    ///
+    /// Returns:
+    ///     List of record dicts ordered by id
+    #[pyo3(signature = (include_vectors = false))]
+    fn to_list(&self, py: Python, include_vectors: bool) -> PyResult<Py<PyList>> {
+        let mut records = self.read_store()?.list_active();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let result_list = PyList::empty(py);
+        for record in records {
+            result_list.append(self.record_dict(py, record, include_vectors)?)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Export ids and vectors of every active record to a numpy `.npz` file
+    ///
+    /// The archive holds an `ids` array of strings (int ids are written as
+    /// their decimal form) and a float32 `vectors` array of shape
+    /// (len(ids), dimension) in the same row order, sorted by id. Load it with
+    /// `numpy.load(path)`. Metadata is not exported; see `to_list`. The whole
+    /// matrix is built in memory, and archives over 4 GiB are not supported.
+    ///
+    /// Args:
+    ///     path: Destination file, conventionally ending in `.npz`
+    ///
+    /// Returns:
+    ///     Number of exported records
+    ///
+    /// Raises:
+    ///     IOError: If the file cannot be written
+    fn export_npz(&self, path: PathBuf) -> PyResult<usize> {
+        let mut records = self.read_store()?.list_active();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut flat = Vec::with_capacity(records.len() * self.dimension);
+        let mut ids = Vec::with_capacity(records.len());
+        for record in records {
+            flat.extend_from_slice(&record.vector);
+            ids.push(record.id);
+        }
+        let count = ids.len();
+        let arrays = [
+            npz::NpyArray::strings("ids", &ids),
+            npz::NpyArray::f32_matrix("vectors", &flat, count, self.dimension),
+        ];
+
+        File::create(&path)
+            .and_then(|file| npz::write(std::io::BufWriter::new(file), &arrays))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyIOError, _>(format!(
+                    "Failed to export {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+        Ok(count)
+    }
+
+    /// Check a filter dict without running it
+    ///
+    /// Applies exactly the parsing `find` does, so API gateways can reject
+    /// user-supplied filters before they reach the store. Unknown operators,
+    /// non-string keys and operands of the wrong type (e.g. `$in` without a
+    /// list, `$gt` with a dict) are reported.
+    ///
+    /// Args:
+    ///     filter: MongoDB-style filter dict, see `find`
+    ///
+    /// Raises:
+    ///     ValueError: Describing the first problem found in the filter
+    fn validate_filter(&self, filter: &Bound<'_, PyDict>) -> PyResult<()> {
+        filter::parse_filter(filter).map(drop)
+    }
+
+    /// Export the store to a directory for fast read-only reopening
+    ///
+    /// Writes the vectors as one flat little-endian f32 file plus a JSON
+    /// metadata sidecar, rows sorted by id; see `open_mmap`. Soft-deleted
+    /// records are left out. An existing export at `path` is overwritten.
+    ///
+    /// Args:
+    ///     path: Destination directory (created if missing)
+    ///
+    /// Returns:
+    ///     Number of exported records
+    ///
+    /// Raises:
+    ///     IOError: If the files cannot be written
+    fn export_mmap(&self, path: PathBuf) -> PyResult<usize> {
+        let mut records = self.read_store()?.list_active();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        mmap_store::export(&path, records, self.dimension, self.metric, self.id_type)
+    }
+
+    /// Open a directory written by `export_mmap` as a read-only `MmapStore`
+    ///
+    /// The vector file is loaded with one sequential read and no ANN index
+    /// is rebuilt, so startup costs little more than reading the file. The
+    /// result is read-optimized: it supports `search` (exact brute-force
+    /// scan), `get` and `len()`, but no mutations. Despite the name, nothing
+    /// stays mapped: the export can be changed or deleted once this returns.
+    ///
+    /// Args:
+    ///     path: Directory written by `export_mmap`
+    ///
+    /// Returns:
+    ///     MmapStore
+    ///
+    /// Raises:
+    ///     IOError: If the export is missing, truncated or of an unknown format
+    #[staticmethod]
+    fn open_mmap(path: PathBuf) -> PyResult<mmap_store::MmapStore> {
+        mmap_store::MmapStore::open(&path)
+    }
+
+    /// Return the records whose metadata matches a filter, without a query vector
+    ///
+    /// This is a metadata lookup (a SQL-like WHERE), not a similarity search:
+    /// results are ordered by id and carry no score. It scans every active
+    /// record, so the cost is O(n) in the store size.
+    ///
+    /// Args:
+    ///     filter: MongoDB-style filter dict, e.g. `{"year": {"$gte": 2020}}`.
+    ///         Supports $eq, $ne, $gt, $gte, $lt, $lte, $in, $nin, $contains,
+    ///         $startswith, $and, $or and $not.
+    ///     limit: Maximum number of records to return (default: all)
+    ///
+    /// Returns:
+    ///     List of dicts with the id and all metadata fields, like `to_list`
+    ///
+    /// Raises:
+    ///     ValueError: If the filter is malformed
+    #[pyo3(signature = (filter, limit = None))]
+    fn find(&self, py: Python, filter: &Bound<'_, PyDict>, limit: Option<usize>) -> PyResult<Py<PyList>> {
+        let expr = filter::parse_filter(filter)?;
+
+        let mut records: Vec<_> = self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .filter(|r| filter::matches(&expr, &r.metadata))
+            .collect();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        records.truncate(limit.unwrap_or(usize::MAX));
+
+        let result_list = PyList::empty(py);
+        for record in records {
+            result_list.append(self.record_dict(py, record, false)?)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// Return the records written after a given time (incremental sync)
+    ///
+    /// Every write stamps the record with a millisecond write time: inserts,
+    /// `update`, `update_many`, `set_metadata_batch`, `set_sparse`,
+    /// `update_vector`, `precompute_norms`, `transform_vectors`,
+    /// `soft_delete` and `undelete`, and `replace_all`/`migrate_dimension`
+    /// for every record. `optimize` keeps the stamps. Records written before
+    /// stamping existed fall back to their last upsert, to the second.
+    /// Deletions are not reported; use `set_on_change` to mirror them.
+    /// Scans every active record.
+    ///
+    /// More records can be written in the same millisecond as the newest
+    /// one returned, and passing its `updated_at` back as is would skip
+    /// them. Pass the largest `updated_at` seen minus 0.001 as the next
+    /// `timestamp` instead, and apply the results by id (e.g.
+    /// `mirror[r["id"]] = r`): the records of that last millisecond come
+    /// back again, and applying them twice is harmless.
+    ///
+    /// Args:
+    ///     timestamp: Unix time in seconds (int or float); records written
+    ///         strictly after it are returned
+    ///
+    /// Returns:
+    ///     List of dicts like `to_list`, plus `updated_at` (unix seconds as
+    ///     a float, millisecond precision), ordered by `updated_at` then id
+    fn modified_since(&self, py: Python, timestamp: f64) -> PyResult<Py<PyList>> {
+        let since = (timestamp * 1000.0).round() as i64;
+        let updated_ms = |r: &Record| {
+            r.metadata
+                .fields
+                .get(UPDATED_FIELD)
+                .and_then(|v| v.as_i64())
+                .unwrap_or(r.created_at.saturating_mul(1000))
+        };
+        let mut records: Vec<(i64, Record)> = self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .map(|r| (updated_ms(&r), r))
+            .filter(|(updated, _)| *updated > since)
+            .collect();
+        records.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.id.cmp(&b.1.id)));
+
+        let result_list = PyList::empty(py);
+        for (updated, record) in records {
+            let dict = self.record_dict(py, record, false)?;
+            dict.set_item("updated_at", updated as f64 / 1000.0)?;
+            result_list.append(dict)?;
+        }
+
+        Ok(result_list.into())
+    }
+
+    /// List the distinct values of a metadata field (facet enumeration)
+    ///
+    /// Scans every active record, so the cost is O(n) in the store size.
+    /// Records without the field are ignored. Values are compared as stored,
+    /// so `1`, `1.0` and `True` are three different values.
+    ///
+    /// Args:
+    ///     field: Metadata field name, e.g. "url" or "author"
+    ///     with_counts: If True, pair each value with the number of records
+    ///         that have it
+    ///
+    /// Returns:
+    ///     List of unique values, or of `(value, count)` tuples with
+    ///     with_counts=True (list and dict values cannot be dict keys).
+    ///     Ordered by type (None, bools, numbers, strings, lists, dicts),
+    ///     then by value, with numbers compared numerically.
+    ///
+    /// Raises:
+    ///     ValueError: If the field is internal (starts with "_")
+    #[pyo3(signature = (field, with_counts = false))]
+    fn distinct(&self, py: Python, field: &str, with_counts: bool) -> PyResult<Py<PyAny>> {
+        if field.starts_with(INTERNAL_PREFIX) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Field '{}' is internal",
+                field
+            )));
+        }
+
+        // Key by the JSON text so that any value type can be de-duplicated
+        let mut counts: HashMap<String, (serde_json::Value, usize)> = HashMap::new();
+        for record in self.read_store()?.list_active() {
+            if let Some(value) = record.metadata.fields.get(field) {
+                counts
+                    .entry(value.to_string())
+                    .or_insert_with(|| (value.clone(), 0))
+                    .1 += 1;
+            }
+        }
+
+        let mut values: Vec<(String, (serde_json::Value, usize))> = counts.into_iter().collect();
+        values.sort_by(|(a_text, (a, _)), (b_text, (b, _))| {
+            json_type_rank(a)
+                .cmp(&json_type_rank(b))
+                .then_with(|| compare_json(a, b))
+                .then_with(|| a_text.cmp(b_text))
+        });
+        let values: Vec<(serde_json::Value, usize)> = values.into_iter().map(|(_, entry)| entry).collect();
+
+        if with_counts {
+            let list = PyList::empty(py);
+            for (value, count) in &values {
+                list.append((json_to_py(py, value)?, count))?;
+            }
+            Ok(list.into_any().unbind())
+        } else {
+            let list = PyList::empty(py);
+            for (value, _) in &values {
+                list.append(json_to_py(py, value)?)?;
+            }
+            Ok(list.into_any().unbind())
+        }
+    }
+
+    /// Component-wise minimum and maximum over all stored vectors
+    ///
+    /// A quick sanity check: values far outside [-1, 1] point at
+    /// un-normalized vectors, NaN at corrupted ones (a NaN component is
+    /// reported as NaN in both lists).
+    ///
+    /// Returns:
+    ///     Tuple `(mins, maxs)` of lists with one float per dimension
+    ///
+    /// Raises:
+    ///     ValueError: If the store is empty
+    fn bounds(&self) -> PyResult<(Vec<f32>, Vec<f32>)> {
+        let records = self.read_store()?.list_active();
+        if records.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "Cannot compute bounds of an empty store",
+            ));
+        }
+
+        let mut mins = vec![f32::INFINITY; self.dimension];
+        let mut maxs = vec![f32::NEG_INFINITY; self.dimension];
+        for record in &records {
+            for ((min, max), &x) in mins.iter_mut().zip(maxs.iter_mut()).zip(&record.vector) {
+                if x.is_nan() || x < *min {
+                    *min = x;
+                }
+                if x.is_nan() || x > *max {
+                    *max = x;
+                }
+            }
+        }
+
+        Ok((mins, maxs))
+    }
+
+    /// Compute and cache the L2 norm of every stored vector
+    ///
+    /// The norm is kept in the internal `_norm` metadata field and used by
+    /// the exhaustive cosine scoring paths (such as `evaluate_recall`) instead
+    /// of recomputing it for every query. This is optional and costs one
+    /// float per record. Records inserted afterwards have no cached norm
+    /// until this is called again; `update_vector` keeps the cache current.
+    /// Fires an "update" change event per record (see `set_on_change`).
+    ///
+    /// Returns:
+    ///     Number of records whose norm was cached
+    fn precompute_norms(&mut self, py: Python) -> PyResult<usize> {
+        self.ensure_writable()?;
+        let mut store = self.write_store()?;
+
+        let records = store.list_active();
+        let mut written = Vec::with_capacity(records.len());
+        let mut failure = None;
+        for record in &records {
+            let mut metadata = record.metadata.clone();
+            metadata
+                .fields
+                .insert(scoring::NORM_FIELD.to_string(), json!(scoring::norm(&record.vector)));
+            stamp_updated(&mut metadata);
+            if let Err(e) = store.update_metadata(&record.id, metadata) {
+                failure = Some(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to update metadata: {}",
+                    e
+                )));
+                break;
+            }
+            written.push(record.id.as_str());
+        }
+        drop(store);
+
+        // Records updated before a backend failure keep their norm, so they
+        // are logged and reported before the error is raised
+        self.notify_many(py, "update", &written)?;
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(written.len()),
+        }
+    }
+
+    /// Replace the vector of an existing document, keeping its metadata
+    ///
+    /// A cached norm (see `precompute_norms`) is recomputed for the new vector,
+    /// and the record keeps its dtype (see `set_vector`).
+    ///
+    /// Args:
+    ///     id: Document identifier
+    ///     vector: New embedding vector
+    ///
+    /// Raises:
+    ///     KeyError: If the document is not in the store
+    fn update_vector(&mut self, py: Python, id: &Bound<'_, PyAny>, mut vector: Vec<f32>) -> PyResult<()> {
+        self.ensure_writable()?;
+        let id = self.key_from_py(id)?;
+        self.check_dimension(&vector)?;
+        self.normalize_incoming(&mut vector);
+        let mut store = self.write_store()?;
 
-        let store = VecStore::open(&temp_dir).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to create vector store: {}",
-                e
-            ))
+        let mut metadata = store
+            .list_active()
+            .into_iter()
+            .find(|r| r.id == id)
+            .map(|r| r.metadata)
+            .ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Document not found: {}", id))
+            })?;
+        let vector = VectorDtype::of(&metadata).quantize(vector);
+        if metadata.fields.contains_key(scoring::NORM_FIELD) {
+            metadata
+                .fields
+                .insert(scoring::NORM_FIELD.to_string(), json!(scoring::norm(&vector)));
+        }
+        stamp_updated(&mut metadata);
+
+        store.upsert(id.clone(), vector, metadata).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to add vector: {}", e))
         })?;
+        drop(store);
 
-        Ok(VectorStore {
-            store: Arc::new(RwLock::new(store)),
-            dimension,
-            temp_path: Some(temp_dir),
-        })
+        self.notify(py, "upsert", Some(&id))
     }
 
-    /// Set (add/update) a document using Python callback for vectorization
-    /// 
-    /// This is a memory-efficient method that:
-    /// 1. Calls the Python callback function with the content
-    /// 2. Gets the vector from the callback
-    /// 3. Stores only the vector and metadata (title, url, summary)
-    /// 4. Discards the content after vectorization
-    /// 
+    /// Attach a sparse vector (e.g. SPLADE term weights) to a document
+    ///
+    /// The vector is stored in the record's metadata as parallel index and
+    /// value arrays, next to its dense vector, and is searched with
+    /// `search_sparse` and `search_hybrid_sparse`. Overwriting the document
+    /// with `set`/`set_vector` drops it; `update` and `update_vector` keep
+    /// it. Passing empty lists removes it.
+    ///
     /// Args:
-    ///     id: Unique identifier for the document
-    ///     content: Document content (will be vectorized via callback then discarded)
-    ///     title: Document title (stored)
-    ///     url: Document URL (stored)
-    ///     summary: Document summary (stored, optional)
-    ///     embedding_callback: Python callable that takes content and returns vector
-    fn set(
-        &mut self,
-        py: Python,
-        id: String,
-        content: String,
-        title: String,
-        url: String,
-        summary: String,
-        embedding_callback: Py<PyAny>,
-    ) -> PyResult<()> {
-        // Call Python callback to get embedding vector
-        let vector: Vec<f32> = embedding_callback.call1(py, (content,))?.extract(py)?;
+    ///     id: Document identifier
+    ///     indices: Non-negative dimension indices (e.g. vocabulary ids), in
+    ///         any order, without duplicates
+    ///     values: One finite weight per index
+    ///
+    /// Raises:
+    ///     KeyError: If the document is not in the store
+    ///     ValueError: If the lists differ in length, an index repeats, or
+    ///         a value is not finite
+    fn set_sparse(&mut self, py: Python, id: &Bound<'_, PyAny>, indices: Vec<u32>, values: Vec<f32>) -> PyResult<()> {
+        self.ensure_writable()?;
+        let id = self.key_from_py(id)?;
+        let sparse = sparse::SparseVector::parse(indices, values)?;
+        let mut store = self.write_store()?;
 
-        // Validate vector dimension
-        if vector.len() != self.dimension {
-            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Vector dimension mismatch. Expected {}, got {}",
-                self.dimension,
-                vector.len()
-            )));
-        }
+        let mut metadata = store
+            .list_active()
+            .into_iter()
+            .find(|r| r.id == id)
+            .map(|r| r.metadata)
+            .ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Document not found: {}", id))
+            })?;
+        sparse.write_to(&mut metadata);
+        stamp_updated(&mut metadata);
 
-        // Create metadata - store title, url, and summary, NOT content
-        // This is the key to memory efficiency!
-        let mut metadata = Metadata {
-            fields: HashMap::new(),
-        };
-        metadata.fields.insert("title".to_string(), json!(title));
-        metadata.fields.insert("url".to_string(), json!(url));
-        metadata.fields.insert("summary".to_string(), json!(summary));
+        store.update_metadata(&id, metadata).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to update: {}", e))
+        })?;
+        drop(store);
 
-        // Upsert vector with metadata
-        // After this point, content is dropped and memory is freed
-        self.store
-            .write()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
-            .upsert(id, vector, metadata)
+        self.notify(py, "update", Some(&id))
+    }
+
+    /// Remove a vector and its metadata (Delete operation)
+    ///
+    /// Args:
+    ///     id: Unique identifier of the document to remove
+    fn rm(&mut self, py: Python, id: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.ensure_writable()?;
+        let id = self.key_from_py(id)?;
+        self.write_store()?
+            .delete(&id)
             .map_err(|e| {
                 PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to add vector: {}",
+                    "Failed to remove vector: {}",
                     e
                 ))
             })?;
 
-        Ok(())
+        self.notify(py, "delete", Some(&id))
     }
 
-    /// Set a document with pre-computed vector (for batch operations)
-    /// 
-    /// Use this when you already have the vector and don't need the callback.
-    /// 
+    /// Hide a document without physically removing it
+    ///
+    /// Soft-deleted documents are excluded from `search`, `get`, `len` and
+    /// every other read path until `undelete` brings them back or
+    /// `purge_soft_deleted` removes them for good.
+    ///
     /// Args:
-    ///     id: Unique identifier for the document
-    ///     vector: Pre-computed embedding vector
-    ///     title: Document title
-    ///     url: Document URL
-    ///     summary: Document summary (optional)
-    fn set_vector(
-        &mut self,
-        id: String,
-        vector: Vec<f32>,
-        title: String,
-        url: String,
-        summary: Option<String>,
-    ) -> PyResult<()> {
-        if vector.len() != self.dimension {
-            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Vector dimension mismatch. Expected {}, got {}",
-                self.dimension,
-                vector.len()
-            )));
+    ///     id: Document identifier
+    ///
+    /// Returns:
+    ///     True if the document was hidden, False if it does not exist or
+    ///     was already soft-deleted
+    fn soft_delete(&mut self, py: Python, id: &Bound<'_, PyAny>) -> PyResult<bool> {
+        self.ensure_writable()?;
+        let id = self.key_from_py(id)?;
+        let mut store = self.write_store()?;
+        let Some(mut metadata) = store.list_active().into_iter().find(|r| r.id == id).map(|r| r.metadata) else {
+            return Ok(false);
+        };
+        stamp_updated(&mut metadata);
+        let hidden = store
+            .update_metadata(&id, metadata)
+            .and_then(|()| store.soft_delete(&id))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to soft-delete: {}", e))
+            })?;
+        drop(store);
+
+        if hidden {
+            self.soft_deleted.fetch_add(1, Ordering::Relaxed);
+            self.notify(py, "soft_delete", Some(&id))?;
         }
+        Ok(hidden)
+    }
 
-        // Create metadata - title, url, and summary, no content
-        let mut metadata = Metadata {
-            fields: HashMap::new(),
+    /// Undo a `soft_delete`
+    ///
+    /// Named `undelete` because `restore` loads a snapshot archive.
+    ///
+    /// Args:
+    ///     id: Document identifier
+    ///
+    /// Returns:
+    ///     True if the document was restored, False if it is not soft-deleted
+    fn undelete(&mut self, py: Python, id: &Bound<'_, PyAny>) -> PyResult<bool> {
+        self.ensure_writable()?;
+        let id = self.key_from_py(id)?;
+        let mut store = self.write_store()?;
+        let Some(mut metadata) = store.list_deleted().into_iter().find(|r| r.id == id).map(|r| r.metadata) else {
+            return Ok(false);
         };
-        metadata.fields.insert("title".to_string(), json!(title));
-        metadata.fields.insert("url".to_string(), json!(url));
-        if let Some(sum) = summary {
-            metadata.fields.insert("summary".to_string(), json!(sum));
+        stamp_updated(&mut metadata);
+        let restored = store
+            .update_metadata(&id, metadata)
+            .and_then(|()| store.restore(&id))
+            .map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to undelete: {}", e))
+            })?;
+        drop(store);
+
+        if restored {
+            self.soft_deleted.fetch_sub(1, Ordering::Relaxed);
+            self.notify(py, "undelete", Some(&id))?;
         }
+        Ok(restored)
+    }
 
-        self.store
-            .write()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
-            .upsert(id, vector, metadata)
-            .map_err(|e| {
+    /// Permanently remove soft-deleted documents
+    ///
+    /// Args:
+    ///     older_than_secs: Only purge documents soft-deleted at least this
+    ///         many seconds ago (default: 0, purge all)
+    ///
+    /// Returns:
+    ///     Number of documents removed
+    #[pyo3(signature = (older_than_secs = 0))]
+    fn purge_soft_deleted(&mut self, py: Python, older_than_secs: u64) -> PyResult<usize> {
+        self.ensure_writable()?;
+        let cutoff = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .saturating_sub(older_than_secs) as i64;
+
+        let mut store = self.write_store()?;
+        let deleted = store.list_deleted();
+        let remaining = deleted.len();
+        let expired: Vec<String> = deleted
+            .into_iter()
+            .filter(|r| r.deleted_at.is_none_or(|at| at <= cutoff))
+            .map(|r| r.id)
+            .collect();
+        for id in &expired {
+            store.remove(id).map_err(|e| {
                 PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to add vector: {}",
+                    "Failed to remove vector: {}",
                     e
                 ))
             })?;
+        }
+        // The scan gives the exact count, correcting any drift from upserts
+        self.soft_deleted.store(remaining - expired.len(), Ordering::Relaxed);
+        drop(store);
 
-        Ok(())
+        let expired: Vec<&str> = expired.iter().map(String::as_str).collect();
+        self.notify_many(py, "delete", &expired)?;
+        Ok(expired.len())
     }
 
-    /// Search for similar vectors with optimized memory usage
+    /// List the ids that start with a prefix
     ///
-    /// Results are automatically sorted by relevance score (highest first).
-    /// Only metadata is returned, vectors are NOT included to save memory.
+    /// For hierarchical id schemes such as "tenant/collection/doc". Ids are
+    /// compared as strings (an int id 123 matches prefix "12"). This scans
+    /// every active record, O(n) per call.
     ///
     /// Args:
-    ///     vector: Query vector (list of floats)
-    ///     k: Number of results to return (default: 5)
+    ///     prefix: String prefix to match; "" matches every id
     ///
     /// Returns:
-    ///     List of dictionaries sorted by score (descending) with:
-    ///     - id: Document identifier
-    ///     - score: Relevance score (higher = more relevant)
-    ///     - title: Document title
-    ///     - url: Document URL
-    ///     - summary: Document summary
-    fn search(&self, py: Python, vector: Vec<f32>, k: Option<usize>) -> PyResult<Py<PyList>> {
-        if vector.len() != self.dimension {
-            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                "Vector dimension mismatch. Expected {}, got {}",
-                self.dimension,
-                vector.len()
-            )));
-        }
+    ///     Matching ids, sorted
+    fn ids_with_prefix(&self, py: Python, prefix: &str) -> PyResult<Py<PyList>> {
+        let mut keys: Vec<String> = self
+            .read_store()?
+            .list_active()
+            .into_iter()
+            .filter(|r| r.id.starts_with(prefix))
+            .map(|r| r.id)
+            .collect();
+        keys.sort_unstable();
 
-        let k = k.unwrap_or(5);
+        let ids = PyList::empty(py);
+        for key in &keys {
+            ids.append(self.key_to_py(py, key)?)?;
+        }
+        Ok(ids.into())
+    }
 
-        // Create query
-        let query = Query {
-            vector,
-            k,
-            filter: None,
-        };
+    /// Remove every document whose id starts with a prefix
+    ///
+    /// Coarse namespace teardown, e.g. `delete_prefix("tenant-a/")`. Like
+    /// `rm`, records are removed for good; soft-deleted ones are left alone.
+    /// Scans every active record (O(n)) under one write lock.
+    ///
+    /// Args:
+    ///     prefix: String prefix to match. Must not be empty; use
+    ///         `replace_all([])` to clear the store.
+    ///
+    /// Returns:
+    ///     Number of documents removed
+    ///
+    /// Raises:
+    ///     ValueError: If prefix is empty
+    fn delete_prefix(&mut self, py: Python, prefix: &str) -> PyResult<usize> {
+        self.ensure_writable()?;
+        if prefix.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "prefix must not be empty",
+            ));
+        }
 
-        // Execute query with read lock for concurrent access
-        // Results are already sorted by vecstore (highest score first)
-        let results = self.store.read()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
-            .query(query)
-            .map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Search failed: {}", e))
+        let mut store = self.write_store()?;
+        let matched: Vec<String> = store
+            .list_active()
+            .into_iter()
+            .filter(|r| r.id.starts_with(prefix))
+            .map(|r| r.id)
+            .collect();
+        for id in &matched {
+            store.delete(id).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
+                    "Failed to remove vector: {}",
+                    e
+                ))
             })?;
+        }
+        drop(store);
 
-        // Convert results to Python list - stream processing for memory efficiency
-        // Create list with pre-allocated capacity
-        let result_list = PyList::empty(py);
+        let matched: Vec<&str> = matched.iter().map(String::as_str).collect();
+        self.notify_many(py, "delete", &matched)?;
+        Ok(matched.len())
+    }
 
-        for result in results {
-            // Create dict only for fields we need - no vectors
-            let dict = PyDict::new(py);
-            dict.set_item("id", &result.id)?;
-            dict.set_item("score", result.score)?;
+    /// Merge metadata fields into many existing documents under one write lock
+    ///
+    /// For pipelines where vectors are inserted first and metadata arrives
+    /// later. Each item's fields are merged into the record's metadata
+    /// (existing fields not named are kept); vectors are untouched. Every
+    /// item is validated before anything is written.
+    ///
+    /// Args:
+    ///     items: List of dicts `{"id": ..., "fields": {...}}`. Field values
+    ///         may be any JSON-compatible Python value; names starting with
+    ///         "_" are reserved for internal bookkeeping and rejected.
+    ///     skip_missing: If True, items whose id is not in the store are
+    ///         skipped; if False (default), such an id raises KeyError
+    ///
+    /// Returns:
+    ///     List of ids that were skipped because they are not in the store
+    ///     (always empty with skip_missing=False)
+    ///
+    /// Raises:
+    ///     KeyError: If an id is not in the store and skip_missing is False
+    ///     ValueError: If an item is malformed or names a reserved field
+    #[pyo3(signature = (items, skip_missing = false))]
+    fn set_metadata_batch(
+        &mut self,
+        py: Python,
+        items: Vec<Bound<'_, PyDict>>,
+        skip_missing: bool,
+    ) -> PyResult<Py<PyList>> {
+        self.ensure_writable()?;
 
-            // Extract metadata fields (title, url, summary - no content, no vector)
-            if let Some(title) = result.metadata.fields.get("title") {
-                if let Some(title_str) = title.as_str() {
-                    dict.set_item("title", title_str)?;
-                } else {
-                    dict.set_item("title", "")?;
-                }
-            } else {
-                dict.set_item("title", "")?;
-            }
-            
-            if let Some(url) = result.metadata.fields.get("url") {
-                if let Some(url_str) = url.as_str() {
-                    dict.set_item("url", url_str)?;
-                } else {
-                    dict.set_item("url", "")?;
+        let malformed = |message: &str| PyErr::new::<pyo3::exceptions::PyValueError, _>(message.to_string());
+        let mut updates = Vec::with_capacity(items.len());
+        for item in &items {
+            let id = item.get_item("id")?.ok_or_else(|| malformed("Item is missing 'id'"))?;
+            let fields = item.get_item("fields")?.ok_or_else(|| malformed("Item is missing 'fields'"))?;
+            let fields = fields
+                .cast::<PyDict>()
+                .map_err(|_| malformed("Item 'fields' must be a dict"))?;
+
+            let mut merged = HashMap::with_capacity(fields.len());
+            for (field, value) in fields.iter() {
+                let field: String = field
+                    .extract()
+                    .map_err(|_| malformed("Field names must be strings"))?;
+                if field.starts_with(INTERNAL_PREFIX) {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "Field '{}' is reserved for internal use",
+                        field
+                    )));
                 }
-            } else {
-                dict.set_item("url", "")?;
+                merged.insert(field, py_to_json(&value)?);
             }
-            
-            if let Some(summary) = result.metadata.fields.get("summary") {
-                if let Some(summary_str) = summary.as_str() {
-                    dict.set_item("summary", summary_str)?;
-                } else {
-                    dict.set_item("summary", "")?;
-                }
+            updates.push((self.key_from_py(&id)?, id, merged));
+        }
+
+        let mut store = self.write_store()?;
+        let mut records: HashMap<String, Metadata> = store
+            .list_active()
+            .into_iter()
+            .map(|r| (r.id, r.metadata))
+            .collect();
+
+        let skipped = PyList::empty(py);
+        let mut found = Vec::with_capacity(updates.len());
+        for (key, id, fields) in updates {
+            if records.contains_key(&key) {
+                found.push((key, fields));
+            } else if skip_missing {
+                skipped.append(id)?;
             } else {
-                dict.set_item("summary", "")?;
+                return Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!(
+                    "Document not found: {}",
+                    key
+                )));
             }
-
-            result_list.append(dict)?;
         }
 
-        Ok(result_list.into())
-    }
-
-    /// Remove a vector and its metadata (Delete operation)
-    ///
-    /// Args:
-    ///     id: Unique identifier of the document to remove
-    fn rm(&mut self, id: String) -> PyResult<()> {
-        self.store.write()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
-            .delete(&id)
-            .map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                    "Failed to remove vector: {}",
-                    e
-                ))
+        // Merge per record first so repeated ids in one batch all apply
+        let mut seen = HashSet::with_capacity(found.len());
+        let mut written = Vec::with_capacity(found.len());
+        for (key, fields) in found {
+            if let Some(metadata) = records.get_mut(&key) {
+                metadata.fields.extend(fields);
+                stamp_updated(metadata);
+            }
+            if seen.insert(key.clone()) {
+                written.push(key);
+            }
+        }
+        for key in &written {
+            self.check_schema(&records[key])?;
+        }
+        for key in &written {
+            store.update_metadata(key, records[key].clone()).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to update: {}", e))
             })?;
+        }
+        drop(store);
 
-        Ok(())
+        let written: Vec<&str> = written.iter().map(String::as_str).collect();
+        self.notify_many(py, "update", &written)?;
+
+        Ok(skipped.into())
     }
 
     /// Update metadata for an existing document
@@ -279,9 +5057,24 @@ impl VectorStore {
     ///     title: New title (optional)
     ///     url: New URL (optional)
     ///     summary: New summary (optional)
-    fn update(&mut self, id: String, title: Option<String>, url: Option<String>, summary: Option<String>) -> PyResult<()> {
-        let mut store = self.store.write()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+    ///     boost: New query-time boost factor used by `search(apply_boost=True)`
+    ///         (optional, must be finite and non-negative)
+    #[pyo3(signature = (id, title = None, url = None, summary = None, boost = None))]
+    fn update(
+        &mut self,
+        py: Python,
+        id: &Bound<'_, PyAny>,
+        title: Option<String>,
+        url: Option<String>,
+        summary: Option<String>,
+        boost: Option<f64>,
+    ) -> PyResult<()> {
+        self.ensure_writable()?;
+        if let Some(b) = boost {
+            check_boost(b)?;
+        }
+        let id = self.key_from_py(id)?;
+        let mut store = self.write_store()?;
         
         let all_records = store.list_active();
         
@@ -300,42 +5093,206 @@ impl VectorStore {
                 if let Some(s) = summary {
                     metadata.fields.insert("summary".to_string(), json!(s));
                 }
+                if let Some(b) = boost {
+                    metadata.fields.insert(BOOST_FIELD.to_string(), json!(b));
+                }
+                self.check_schema(&metadata)?;
+                stamp_updated(&mut metadata);
                 
                 // Update in store
                 store.update_metadata(&id, metadata)
                     .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to update: {}", e)))?;
+                drop(store);
                 
-                return Ok(());
+                return self.notify(py, "update", Some(&id));
             }
         }
         
         Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Document not found: {}", id)))
     }
 
+    /// Apply many `update` calls under one write lock and a single scan
+    ///
+    /// `update` scans the whole store for every call, so N updates cost
+    /// O(N * n); this indexes the store once. Every update is validated and
+    /// every id looked up before anything is written.
+    ///
+    /// Args:
+    ///     updates: List of dicts with an "id" plus any of the fields
+    ///         `update` accepts: "title", "url", "summary" (str) and "boost"
+    ///         (finite, non-negative number). Fields not given are kept.
+    ///         Arbitrary metadata fields go through `set_metadata_batch`.
+    ///
+    /// Returns:
+    ///     Number of updates applied
+    ///
+    /// Raises:
+    ///     KeyError: If an id is not in the store
+    ///     ValueError: If an update is missing "id" or has an unknown or
+    ///         invalid field
+    fn update_many(&mut self, py: Python, updates: Vec<Bound<'_, PyDict>>) -> PyResult<usize> {
+        self.ensure_writable()?;
+
+        let mut parsed = Vec::with_capacity(updates.len());
+        for update in &updates {
+            let mut key = None;
+            let mut fields = Vec::with_capacity(update.len());
+            for (name, value) in update.iter() {
+                let name: String = name.extract()?;
+                match name.as_str() {
+                    "id" => key = Some(self.key_from_py(&value)?),
+                    "title" | "url" | "summary" => fields.push((name, json!(value.extract::<String>()?))),
+                    "boost" => {
+                        let boost: f64 = value.extract()?;
+                        check_boost(boost)?;
+                        fields.push((BOOST_FIELD.to_string(), json!(boost)));
+                    }
+                    other => {
+                        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                            "Unknown update field '{}'. Expected 'title', 'url', 'summary' or 'boost'",
+                            other
+                        )));
+                    }
+                }
+            }
+            let key = key.ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>("Update is missing 'id'")
+            })?;
+            parsed.push((key, fields));
+        }
+
+        let mut store = self.write_store()?;
+        let mut records: HashMap<String, Metadata> = store
+            .list_active()
+            .into_iter()
+            .map(|r| (r.id, r.metadata))
+            .collect();
+
+        // Merge per record first so repeated ids in one call all apply
+        let mut seen = HashSet::with_capacity(parsed.len());
+        let mut written = Vec::with_capacity(parsed.len());
+        for (key, fields) in &parsed {
+            let metadata = records.get_mut(key).ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!("Document not found: {}", key))
+            })?;
+            metadata.fields.extend(fields.iter().cloned());
+            stamp_updated(metadata);
+            if seen.insert(key) {
+                written.push(key);
+            }
+        }
+        for key in &written {
+            self.check_schema(&records[*key])?;
+        }
+        for key in &written {
+            store.update_metadata(key, records[*key].clone()).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to update: {}", e))
+            })?;
+        }
+        drop(store);
+
+        let written: Vec<&str> = written.iter().map(|key| key.as_str()).collect();
+        self.notify_many(py, "update", &written)?;
+
+        Ok(parsed.len())
+    }
+
+    /// Register a callback fired after every successful mutation
+    ///
+    /// The callback receives an event dict `{"op": ..., "id": ...}` where op
+    /// is "upsert" (`set`, `set_vector`, the batch setters, `update_vector`),
+    /// "update" (`update`, `update_many`, `set_metadata_batch`,
+    /// `set_sparse`, `precompute_norms`), "delete" (`rm`, `delete_prefix`,
+    /// `purge_soft_deleted`), "soft_delete", "undelete" or
+    /// "replace_all" (`replace_all`, `migrate_dimension`,
+    /// `transform_vectors`, `optimize`; id None).
+    /// Batch methods fire one event per written id. It runs with the GIL
+    /// held, after the store's write lock is released. The calling method is
+    /// still executing, so the callback must not call back into this same
+    /// store object; use it to invalidate caches or mirror to another store.
+    /// An exception raised by the callback propagates to the caller, after
+    /// the mutation has already been applied.
+    ///
+    /// Args:
+    ///     callback: Callable taking the event dict, or None to unregister
+    #[pyo3(signature = (callback))]
+    fn set_on_change(&mut self, callback: Option<Py<PyAny>>) {
+        self.on_change = callback;
+    }
+
     /// Get the number of vectors in the store
-    fn len(&self) -> PyResult<usize> {
-        Ok(self.store.read()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
-            .len())
+    ///
+    /// Args:
+    ///     filter: Optional filter dict (same syntax as `find`); when given,
+    ///         only matching records are counted, which scans the store
+    ///
+    /// Raises:
+    ///     ValueError: If the filter is malformed
+    #[pyo3(signature = (filter = None))]
+    fn len(&self, filter: Option<&Bound<'_, PyDict>>) -> PyResult<usize> {
+        let Some(filter) = filter else {
+            return Ok(self.read_store()?.len());
+        };
+        let expr = filter::parse_filter(filter)?;
+        Ok(self
+            .read_store()?
+            .list_active()
+            .iter()
+            .filter(|r| filter::matches(&expr, &r.metadata))
+            .count())
     }
 
     /// Check if the store is empty
-    fn is_empty(&self) -> PyResult<bool> {
-        Ok(self.store.read()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?
-            .is_empty())
+    ///
+    /// Args:
+    ///     filter: Optional filter dict (same syntax as `find`); when given,
+    ///         checks whether any record matches
+    ///
+    /// Raises:
+    ///     ValueError: If the filter is malformed
+    #[pyo3(signature = (filter = None))]
+    fn is_empty(&self, filter: Option<&Bound<'_, PyDict>>) -> PyResult<bool> {
+        let Some(filter) = filter else {
+            return Ok(self.read_store()?.is_empty());
+        };
+        let expr = filter::parse_filter(filter)?;
+        Ok(!self
+            .read_store()?
+            .list_active()
+            .iter()
+            .any(|r| filter::matches(&expr, &r.metadata)))
     }
 
     /// Get metadata for a specific document (Read operation)
     ///
     /// Args:
     ///     id: Document identifier
+    ///     include_content: Add a "content" key with the decompressed content
+    ///         (None unless the store was created with store_content="compressed")
+    ///     include_missing_as_none: Always include title, url and summary,
+    ///         as None when the record lacks them (by default a missing title
+    ///         or url is left out and a missing summary is returned as "")
+    ///     strict: If True, a missing id raises KeyError, like `update` and
+    ///         `rm`. If False (default), a missing id returns None.
     ///
     /// Returns:
-    ///     Dictionary containing title, url, and summary (no content)
-    fn get(&self, py: Python, id: String) -> PyResult<Py<PyAny>> {
-        let store = self.store.read()
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+    ///     Dictionary containing title, url, summary and the vector dtype
+    ///     ("f32", "f16" or "bf16", see `set_vector`), or None for a missing
+    ///     id when strict is False
+    ///
+    /// Raises:
+    ///     KeyError: If strict is True and the document is not in the store
+    #[pyo3(signature = (id, include_content = false, include_missing_as_none = false, strict = false))]
+    fn get(
+        &self,
+        py: Python,
+        id: &Bound<'_, PyAny>,
+        include_content: bool,
+        include_missing_as_none: bool,
+        strict: bool,
+    ) -> PyResult<Py<PyAny>> {
+        let id = self.key_from_py(id)?;
+        let store = self.read_store()?;
         let all_records = store.list_active();
 
         // Find the record with matching id
@@ -343,38 +5300,44 @@ impl VectorStore {
             if record.id == id {
                 let dict = PyDict::new(py);
 
-                if let Some(title) = record.metadata.fields.get("title") {
-                    if let Some(title_str) = title.as_str() {
-                        dict.set_item("title", title_str)?;
-                    }
+                if let Some(title_str) = record.metadata.fields.get("title").and_then(|v| v.as_str()) {
+                    dict.set_item("title", title_str)?;
                 }
-                if let Some(url) = record.metadata.fields.get("url") {
-                    if let Some(url_str) = url.as_str() {
-                        dict.set_item("url", url_str)?;
-                    }
+                if let Some(url_str) = record.metadata.fields.get("url").and_then(|v| v.as_str()) {
+                    dict.set_item("url", url_str)?;
                 }
-                if let Some(summary) = record.metadata.fields.get("summary") {
-                    if let Some(summary_str) = summary.as_str() {
-                        dict.set_item("summary", summary_str)?;
-                    }
+                // Always present, like in `search` results (see `document_metadata`)
+                dict.set_item("summary", meta_str(&record.metadata, "summary"))?;
+                if include_missing_as_none {
+                    set_missing_to_none(&dict, &record.metadata)?;
+                }
+                dict.set_item("dtype", VectorDtype::of(&record.metadata).name())?;
+                if include_content {
+                    dict.set_item("content", decompress_content(&record.metadata)?)?;
                 }
 
                 return Ok(dict.into());
             }
         }
 
+        if strict {
+            return Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!(
+                "Document not found: {}",
+                id
+            )));
+        }
         Ok(py.None())
     }
     
     /// Alias for get() to maintain backward compatibility
-    fn get_metadata(&self, py: Python, id: String) -> PyResult<Py<PyAny>> {
-        self.get(py, id)
+    fn get_metadata(&self, py: Python, id: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        self.get(py, id, false, false, false)
     }
 }
 
 impl Drop for VectorStore {
     fn drop(&mut self) {
-        // Clean up temporary directory
+        // Clean up temporary directory, unless close() already did
         if let Some(ref path) = self.temp_path {
             let _ = std::fs::remove_dir_all(path);
         }
@@ -384,6 +5347,18 @@ impl Drop for VectorStore {
 /// PyO3 module definition
 #[pymodule]
 fn tf_rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    logging::init();
     m.add_class::<VectorStore>()?;
+    m.add_class::<result::SearchResult>()?;
+    m.add_class::<result::SearchIter>()?;
+    m.add_class::<result::RankedCursor>()?;
+    m.add_class::<mmap_store::MmapStore>()?;
+    m.add_function(wrap_pyfunction!(embedding::infer_dimension, m)?)?;
+    m.add_function(wrap_pyfunction!(fusion::fuse_results, m)?)?;
+    m.add_function(wrap_pyfunction!(fusion::search_ensemble, m)?)?;
+    m.add_function(wrap_pyfunction!(logging::set_log_level, m)?)?;
+    m.add_function(wrap_pyfunction!(vector_ops::cosine, m)?)?;
+    m.add_function(wrap_pyfunction!(vector_ops::dot, m)?)?;
+    m.add_function(wrap_pyfunction!(vector_ops::l2, m)?)?;
     Ok(())
 }
@@ -0,0 +1,90 @@
+//! Bridge from the `log` facade to Python's `logging` module
+//!
+//! Records are forwarded to the `tf_rust` Python logger, so applications
+//! configure handlers and formatting there as usual. Only `warn` and above
+//! are emitted until `set_log_level` raises the verbosity.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pyo3::prelude::*;
+
+/// Name of the Python logger records are routed to
+const LOGGER_NAME: &str = "tf_rust";
+
+/// Forwards every enabled record to `logging.getLogger("tf_rust")`
+///
+/// Logging acquires the GIL, so records must only be emitted from the thread
+/// that called into the binding, never from inside a rayon pool.
+struct PyLogger;
+
+static LOGGER: PyLogger = PyLogger;
+
+impl Log for PyLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // A failing handler must never turn a successful call into an error
+        let _ = Python::attach(|py| -> PyResult<()> {
+            py.import("logging")?
+                .call_method1("getLogger", (LOGGER_NAME,))?
+                .call_method1("log", (python_level(record.level()), record.args().to_string()))?;
+            Ok(())
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// Python `logging` level number for a `log` level
+fn python_level(level: Level) -> u32 {
+    match level {
+        Level::Error => 40,
+        Level::Warn => 30,
+        Level::Info => 20,
+        Level::Debug | Level::Trace => 10,
+    }
+}
+
+/// Install the Python bridge as the global logger, called once at module init
+///
+/// Another logger installed first (e.g. by an embedding application) is kept.
+pub(crate) fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Warn);
+    }
+}
+
+/// Set the most verbose level the extension emits
+///
+/// Records below this level are dropped in Rust before the GIL is touched,
+/// so the Python logger's own level can stay at its default.
+///
+/// Args:
+///     level: "off", "error", "warn" (default), "info", "debug" or "trace";
+///         Python's names "warning" and "critical" are accepted too
+///
+/// Raises:
+///     ValueError: If the level name is unknown
+#[pyfunction]
+pub(crate) fn set_log_level(level: &str) -> PyResult<()> {
+    let filter = match level.to_ascii_lowercase().as_str() {
+        "off" => LevelFilter::Off,
+        "error" | "critical" => LevelFilter::Error,
+        "warn" | "warning" => LevelFilter::Warn,
+        "info" => LevelFilter::Info,
+        "debug" => LevelFilter::Debug,
+        "trace" => LevelFilter::Trace,
+        other => {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Invalid log level '{}'. Expected 'off', 'error', 'warn', 'info', 'debug' or 'trace'",
+                other
+            )))
+        }
+    };
+    log::set_max_level(filter);
+    Ok(())
+}
@@ -0,0 +1,241 @@
+//! Read-only stores over a flat vector file
+//!
+//! `VectorStore.export_mmap` writes a directory holding:
+//!
+//! ```text
+//! vectors.f32     row-major little-endian f32 matrix, one row per record
+//! metadata.json   dimension, metric, id type, ids and metadata, in row order
+//! ```
+//!
+//! `VectorStore.open_mmap` loads `vectors.f32` with one sequential read and
+//! rebuilds no index. Searches are exact brute-force scans. The file used to
+//! be memory-mapped, hence the names; a mapping crashes the process with
+//! SIGBUS if the file is truncated while open, so it is read instead.
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use vecstore::{Distance, Metadata, Record};
+
+use crate::{IdType, INTERNAL_PREFIX, json_to_py, meta_str, scoring};
+
+const VECTORS_FILE: &str = "vectors.f32";
+const METADATA_FILE: &str = "metadata.json";
+
+/// Version of the on-disk layout, bumped on incompatible changes
+const FORMAT_VERSION: u32 = 1;
+
+/// Contents of `metadata.json`
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    format: u32,
+    dimension: usize,
+    metric: String,
+    id_type: String,
+    ids: Vec<String>,
+    metadata: Vec<Metadata>,
+}
+
+fn io_error(path: &Path, e: impl std::fmt::Display) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("{}: {}", path.display(), e))
+}
+
+/// Write `records` (already in the desired row order) into directory `dir`
+pub(crate) fn export(
+    dir: &Path,
+    records: Vec<Record>,
+    dimension: usize,
+    metric: Distance,
+    id_type: IdType,
+) -> PyResult<usize> {
+    std::fs::create_dir_all(dir).map_err(|e| io_error(dir, e))?;
+
+    let vectors_path = dir.join(VECTORS_FILE);
+    let mut writer = BufWriter::new(File::create(&vectors_path).map_err(|e| io_error(&vectors_path, e))?);
+    let mut ids = Vec::with_capacity(records.len());
+    let mut metadata = Vec::with_capacity(records.len());
+    for record in records {
+        for x in &record.vector {
+            writer.write_all(&x.to_le_bytes()).map_err(|e| io_error(&vectors_path, e))?;
+        }
+        ids.push(record.id);
+        metadata.push(record.metadata);
+    }
+    writer.flush().map_err(|e| io_error(&vectors_path, e))?;
+
+    let count = ids.len();
+    let sidecar = Sidecar {
+        format: FORMAT_VERSION,
+        dimension,
+        metric: metric.name().to_string(),
+        id_type: id_type.name().to_string(),
+        ids,
+        metadata,
+    };
+    let metadata_path = dir.join(METADATA_FILE);
+    let file = File::create(&metadata_path).map_err(|e| io_error(&metadata_path, e))?;
+    serde_json::to_writer(BufWriter::new(file), &sidecar).map_err(|e| io_error(&metadata_path, e))?;
+
+    Ok(count)
+}
+
+/// Read-only store over an `export_mmap` directory, see `VectorStore.open_mmap`
+///
+/// Supports `search`, `get` and `len()`. It has no ANN index: every search
+/// scans all vectors exactly (in parallel), which is fast to open and
+/// accurate but scales linearly with the store size.
+#[pyclass(module = "tf_rust")]
+pub(crate) struct MmapStore {
+    /// Every vector, row after row
+    vectors: Vec<f32>,
+    dimension: usize,
+    metric: Distance,
+    id_type: IdType,
+    ids: Vec<String>,
+    metadata: Vec<Metadata>,
+    rows: HashMap<String, usize>,
+}
+
+impl MmapStore {
+    pub(crate) fn open(dir: &Path) -> PyResult<Self> {
+        let metadata_path = dir.join(METADATA_FILE);
+        let file = File::open(&metadata_path).map_err(|e| io_error(&metadata_path, e))?;
+        let sidecar: Sidecar = serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|e| io_error(&metadata_path, e))?;
+        if sidecar.format != FORMAT_VERSION || sidecar.ids.len() != sidecar.metadata.len() {
+            return Err(io_error(&metadata_path, "unsupported or corrupted mmap export"));
+        }
+        let metric = Distance::from_str(&sidecar.metric).map_err(|e| io_error(&metadata_path, e))?;
+        let id_type = IdType::parse(&sidecar.id_type)?;
+
+        let vectors_path = dir.join(VECTORS_FILE);
+        let mut file = File::open(&vectors_path).map_err(|e| io_error(&vectors_path, e))?;
+        let expected = sidecar.ids.len() * sidecar.dimension * 4;
+        let mut bytes = Vec::with_capacity(expected);
+        file.read_to_end(&mut bytes).map_err(|e| io_error(&vectors_path, e))?;
+        if bytes.len() != expected {
+            return Err(io_error(
+                &vectors_path,
+                format!("expected {} bytes for {} vectors, found {}", expected, sidecar.ids.len(), bytes.len()),
+            ));
+        }
+        let vectors = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        let rows = sidecar.ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+        Ok(MmapStore {
+            vectors,
+            dimension: sidecar.dimension,
+            metric,
+            id_type,
+            ids: sidecar.ids,
+            metadata: sidecar.metadata,
+            rows,
+        })
+    }
+
+    /// Iterate the rows in parallel
+    fn par_rows(&self) -> impl IndexedParallelIterator<Item = &[f32]> {
+        // chunks_exact panics on a zero size; a zero-dimension export has no vectors
+        self.vectors.par_chunks_exact(self.dimension.max(1))
+    }
+}
+
+#[pymethods]
+impl MmapStore {
+    /// Exact search over the exported vectors
+    ///
+    /// Args:
+    ///     vector: Query vector (list of floats)
+    ///     k: Number of results to return (default: 5)
+    ///
+    /// Returns:
+    ///     List of result dicts (same shape as `VectorStore.search`) sorted by
+    ///     score (descending), ties by id
+    ///
+    /// Raises:
+    ///     ValueError: If k is 0 or the vector has the wrong dimension
+    #[pyo3(signature = (vector, k = 5))]
+    fn search(&self, py: Python, vector: Vec<f32>, k: usize) -> PyResult<Py<PyList>> {
+        if k == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("k must be at least 1"));
+        }
+        if vector.len() != self.dimension {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Vector dimension mismatch. Expected {}, got {}",
+                self.dimension,
+                vector.len()
+            )));
+        }
+
+        let metric = self.metric;
+        let mut scored: Vec<(usize, f32)> = py.detach(|| {
+            self.par_rows()
+                .enumerate()
+                .map(|(i, row)| (i, scoring::similarity(metric, &vector, row)))
+                .collect()
+        });
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| self.ids[a.0].cmp(&self.ids[b.0])));
+        scored.truncate(k);
+
+        let result_list = PyList::empty(py);
+        for (i, score) in scored {
+            let metadata = &self.metadata[i];
+            let dict = PyDict::new(py);
+            dict.set_item("id", self.id_type.key_to_py(py, &self.ids[i])?)?;
+            dict.set_item("score", score)?;
+            dict.set_item("title", meta_str(metadata, "title"))?;
+            dict.set_item("url", meta_str(metadata, "url"))?;
+            dict.set_item("summary", meta_str(metadata, "summary"))?;
+            result_list.append(dict)?;
+        }
+        Ok(result_list.into())
+    }
+
+    /// Return a record's id, public metadata fields and vector
+    ///
+    /// Raises:
+    ///     KeyError: If the document is not in the export
+    fn get(&self, py: Python, id: &Bound<'_, PyAny>) -> PyResult<Py<PyDict>> {
+        let key = self.id_type.key_from_py(id)?;
+        let Some(&i) = self.rows.get(&key) else {
+            return Err(PyErr::new::<pyo3::exceptions::PyKeyError, _>(format!(
+                "Document not found: {}",
+                key
+            )));
+        };
+
+        let dict = PyDict::new(py);
+        dict.set_item("id", self.id_type.key_to_py(py, &key)?)?;
+        for (field, value) in &self.metadata[i].fields {
+            if !field.starts_with(INTERNAL_PREFIX) {
+                dict.set_item(field, json_to_py(py, value)?)?;
+            }
+        }
+        dict.set_item("vector", &self.vectors[i * self.dimension..(i + 1) * self.dimension])?;
+        Ok(dict.into())
+    }
+
+    /// Vector dimension of the export
+    #[getter]
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Name of the distance metric the export was written with
+    #[getter]
+    fn metric(&self) -> &'static str {
+        self.metric.name()
+    }
+
+    fn __len__(&self) -> usize {
+        self.ids.len()
+    }
+}
@@ -0,0 +1,213 @@
+//! Minimal writer for numpy `.npz` archives
+//!
+//! An `.npz` file is an uncompressed ZIP archive of `.npy` files, one per
+//! array. Only what `VectorStore.export_npz` needs is supported: 1-D unicode
+//! string arrays and 2-D float32 arrays, without ZIP64 (each archive must stay
+//! under 4 GiB).
+
+use flate2::Crc;
+use std::io::{self, Write};
+
+/// One array in `.npy` format, ready to be stored in the archive
+pub(crate) struct NpyArray {
+    name: String,
+    bytes: Vec<u8>,
+}
+
+impl NpyArray {
+    /// 1-D array of strings, stored as fixed-width UTF-32 (`<U{n}`)
+    pub(crate) fn strings(name: &str, values: &[String]) -> Self {
+        let width = values.iter().map(|v| v.chars().count()).max().unwrap_or(0).max(1);
+        let mut data = Vec::with_capacity(values.len() * width * 4);
+        for value in values {
+            let chars = value.chars().count();
+            for c in value.chars() {
+                data.extend_from_slice(&(c as u32).to_le_bytes());
+            }
+            data.resize(data.len() + (width - chars) * 4, 0);
+        }
+        Self::new(name, &format!("<U{}", width), &format!("({},)", values.len()), data)
+    }
+
+    /// Row-major 2-D float32 array of shape (rows, cols)
+    pub(crate) fn f32_matrix(name: &str, values: &[f32], rows: usize, cols: usize) -> Self {
+        let mut data = Vec::with_capacity(values.len() * 4);
+        for value in values {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        Self::new(name, "<f4", &format!("({}, {})", rows, cols), data)
+    }
+
+    /// Prefix `data` with an `.npy` version 1.0 header
+    fn new(name: &str, descr: &str, shape: &str, data: Vec<u8>) -> Self {
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            descr, shape
+        );
+        // Magic (6) + version (2) + header length (2) + header, padded with
+        // spaces and a trailing newline to a multiple of 64 bytes
+        let unpadded = 10 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+        header.push('\n');
+
+        let mut bytes = Vec::with_capacity(10 + header.len() + data.len());
+        bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(&data);
+        NpyArray {
+            name: format!("{}.npy", name),
+            bytes,
+        }
+    }
+}
+
+/// Write `arrays` as a stored (uncompressed) ZIP archive
+pub(crate) fn write<W: Write>(mut out: W, arrays: &[NpyArray]) -> io::Result<()> {
+    let too_large = || io::Error::other("archive exceeds 4 GiB, which needs ZIP64");
+    let mut central = Vec::new();
+    let mut offset: u32 = 0;
+
+    for array in arrays {
+        let mut crc = Crc::new();
+        crc.update(&array.bytes);
+        let size = u32::try_from(array.bytes.len()).map_err(|_| too_large())?;
+        let name = array.name.as_bytes();
+
+        let mut local = Vec::with_capacity(30 + name.len());
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        write_entry_fields(&mut local, crc.sum(), size, name.len() as u16);
+        local.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        local.extend_from_slice(name);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        write_entry_fields(&mut central, crc.sum(), size, name.len() as u16);
+        central.extend_from_slice(&[0u8; 12]); // extra, comment, disk, attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name);
+
+        out.write_all(&local)?;
+        out.write_all(&array.bytes)?;
+        offset = offset
+            .checked_add(local.len() as u32)
+            .and_then(|o| o.checked_add(size))
+            .ok_or_else(too_large)?;
+    }
+
+    let entries = arrays.len() as u16;
+    let mut end = Vec::with_capacity(22);
+    end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    end.extend_from_slice(&[0u8; 4]); // disk numbers
+    end.extend_from_slice(&entries.to_le_bytes());
+    end.extend_from_slice(&entries.to_le_bytes());
+    end.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    end.extend_from_slice(&offset.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+    out.write_all(&central)?;
+    out.write_all(&end)?;
+    out.flush()
+}
+
+/// Header fields shared by local and central directory entries, from
+/// "version needed" through the file name length
+fn write_entry_fields(buf: &mut Vec<u8>, crc: u32, size: u32, name_len: u16) {
+    buf.extend_from_slice(&20u16.to_le_bytes()); // version needed
+    buf.extend_from_slice(&0u16.to_le_bytes()); // flags
+    buf.extend_from_slice(&0u16.to_le_bytes()); // method: stored
+    buf.extend_from_slice(&0u16.to_le_bytes()); // modification time
+    buf.extend_from_slice(&0x0021u16.to_le_bytes()); // modification date: 1980-01-01
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf.extend_from_slice(&size.to_le_bytes()); // compressed size
+    buf.extend_from_slice(&size.to_le_bytes()); // uncompressed size
+    buf.extend_from_slice(&name_len.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn npy_header_is_padded_to_64_bytes() {
+        let array = NpyArray::f32_matrix("vectors", &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3);
+        assert_eq!(array.name, "vectors.npy");
+        assert_eq!(&array.bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16_at(&array.bytes, 8) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+
+        let header = std::str::from_utf8(&array.bytes[10..10 + header_len]).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with('\n'));
+        let data = &array.bytes[10 + header_len..];
+        assert_eq!(data.len(), 24);
+        assert_eq!(f32::from_le_bytes(data[20..24].try_into().unwrap()), 6.0);
+    }
+
+    #[test]
+    fn strings_are_fixed_width_utf32() {
+        let array = NpyArray::strings("ids", &["ab".to_string(), "é".to_string()]);
+        let header_len = u16_at(&array.bytes, 8) as usize;
+        let header = std::str::from_utf8(&array.bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'descr': '<U2'"));
+        assert!(header.contains("'shape': (2,)"));
+
+        let data = &array.bytes[10 + header_len..];
+        let chars: Vec<u32> = data.chunks(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect();
+        assert_eq!(chars, ['a' as u32, 'b' as u32, 'é' as u32, 0]);
+    }
+
+    #[test]
+    fn empty_string_array_has_width_one() {
+        let array = NpyArray::strings("ids", &[]);
+        let header_len = u16_at(&array.bytes, 8) as usize;
+        let header = std::str::from_utf8(&array.bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'descr': '<U1'") && header.contains("'shape': (0,)"));
+    }
+
+    #[test]
+    fn archive_directory_points_at_every_entry() {
+        let arrays = [
+            NpyArray::strings("ids", &["a".to_string()]),
+            NpyArray::f32_matrix("vectors", &[1.0, 2.0], 1, 2),
+        ];
+        let mut zip = Vec::new();
+        write(&mut zip, &arrays).unwrap();
+
+        // End of central directory record
+        let end = zip.len() - 22;
+        assert_eq!(u32_at(&zip, end), 0x0605_4b50);
+        assert_eq!(u16_at(&zip, end + 10), 2);
+        let central_size = u32_at(&zip, end + 12) as usize;
+        let central_offset = u32_at(&zip, end + 16) as usize;
+        assert_eq!(central_offset + central_size, end);
+
+        let mut at = central_offset;
+        for array in &arrays {
+            assert_eq!(u32_at(&zip, at), 0x0201_4b50);
+            let size = u32_at(&zip, at + 20) as usize;
+            let name_len = u16_at(&zip, at + 28) as usize;
+            let local = u32_at(&zip, at + 42) as usize;
+            assert_eq!(&zip[at + 46..at + 46 + name_len], array.name.as_bytes());
+            assert_eq!(size, array.bytes.len());
+
+            // The local header stores the same name, followed by the data
+            assert_eq!(u32_at(&zip, local), 0x0403_4b50);
+            let data = local + 30 + name_len;
+            assert_eq!(&zip[data..data + size], &array.bytes[..]);
+
+            let mut crc = Crc::new();
+            crc.update(&array.bytes);
+            assert_eq!(u32_at(&zip, at + 16), crc.sum());
+            at += 46 + name_len;
+        }
+    }
+}
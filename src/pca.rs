@@ -0,0 +1,187 @@
+//! Principal component variances for `VectorStore.pca_summary`
+//!
+//! The covariance matrix is never formed: power iteration only needs the
+//! product `C v = 1/n * sum((x - mean) * ((x - mean) . v))`, which is one
+//! O(n * d) pass over the records. Each component is found on the subspace
+//! orthogonal to the previous ones (deflation), so k components cost
+//! O(k * iterations * n * d) time and O(k * d) memory.
+
+use rayon::prelude::*;
+use vecstore::Record;
+
+const MAX_ITERATIONS: usize = 200;
+/// Relative change of the eigenvalue estimate at which iteration stops
+const TOLERANCE: f64 = 1e-9;
+
+/// `C v` for the covariance C of the centered record vectors
+fn covariance_product(records: &[Record], mean: &[f64], v: &[f64]) -> Vec<f64> {
+    let d = mean.len();
+    let sum = records
+        .par_iter()
+        .fold(
+            || vec![0.0f64; d],
+            |mut acc, record| {
+                let projection: f64 = record
+                    .vector
+                    .iter()
+                    .zip(mean)
+                    .zip(v)
+                    .map(|((&x, m), v)| (x as f64 - m) * v)
+                    .sum();
+                for ((a, &x), m) in acc.iter_mut().zip(&record.vector).zip(mean) {
+                    *a += (x as f64 - m) * projection;
+                }
+                acc
+            },
+        )
+        .reduce(
+            || vec![0.0f64; d],
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                a
+            },
+        );
+    let n = records.len() as f64;
+    sum.into_iter().map(|s| s / n).collect()
+}
+
+/// Remove the components along `basis` (orthonormal) and scale to unit length
+///
+/// Returns false if nothing is left, i.e. `v` lies in the span of `basis`.
+fn orthonormalize(v: &mut [f64], basis: &[Vec<f64>]) -> bool {
+    for b in basis {
+        let overlap: f64 = v.iter().zip(b).map(|(x, y)| x * y).sum();
+        v.iter_mut().zip(b).for_each(|(x, y)| *x -= overlap * y);
+    }
+    let norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm <= f64::EPSILON {
+        return false;
+    }
+    v.iter_mut().for_each(|x| *x /= norm);
+    true
+}
+
+/// Variances along the top principal components, plus the total variance
+///
+/// Returns at most `n_components` variances in descending order; fewer
+/// when the vectors span fewer dimensions. Population (1/n) covariance is
+/// used, as in sklearn's explained-variance ratios.
+pub(crate) fn principal_variances(records: &[Record], n_components: usize) -> (Vec<f64>, f64) {
+    let Some(first) = records.first() else {
+        return (Vec::new(), 0.0);
+    };
+    let d = first.vector.len();
+    let n = records.len() as f64;
+
+    let mut mean = vec![0.0f64; d];
+    for record in records {
+        mean.iter_mut().zip(&record.vector).for_each(|(m, &x)| *m += x as f64);
+    }
+    mean.iter_mut().for_each(|m| *m /= n);
+
+    // Trace of the covariance matrix
+    let total: f64 = records
+        .par_iter()
+        .map(|r| r.vector.iter().zip(&mean).map(|(&x, m)| (x as f64 - m).powi(2)).sum::<f64>())
+        .sum::<f64>()
+        / n;
+
+    let mut basis: Vec<Vec<f64>> = Vec::with_capacity(n_components);
+    let mut variances = Vec::with_capacity(n_components);
+    while variances.len() < n_components.min(d) {
+        // Deterministic start that is unlikely to be orthogonal to any component
+        let mut v: Vec<f64> = (0..d).map(|j| 1.0 + ((j + variances.len()) % 7) as f64 / 7.0).collect();
+        if !orthonormalize(&mut v, &basis) {
+            break;
+        }
+
+        let mut eigenvalue = 0.0;
+        for _ in 0..MAX_ITERATIONS {
+            let mut next = covariance_product(records, &mean, &v);
+            // Rayleigh quotient v . Cv with unit v
+            let estimate: f64 = next.iter().zip(&v).map(|(a, b)| a * b).sum();
+            if !orthonormalize(&mut next, &basis) {
+                eigenvalue = 0.0;
+                break;
+            }
+            v = next;
+            let converged = (estimate - eigenvalue).abs() <= TOLERANCE * estimate.abs().max(f64::MIN_POSITIVE);
+            eigenvalue = estimate;
+            if converged {
+                break;
+            }
+        }
+
+        // Remaining variance is numerical noise once the data's span is exhausted
+        if eigenvalue <= total * TOLERANCE {
+            break;
+        }
+        variances.push(eigenvalue);
+        basis.push(v);
+    }
+
+    (variances, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use vecstore::Metadata;
+
+    fn records(vectors: &[[f32; 3]]) -> Vec<Record> {
+        vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| Record {
+                id: i.to_string(),
+                vector: v.to_vec(),
+                metadata: Metadata { fields: HashMap::new() },
+                created_at: 0,
+                deleted: false,
+                deleted_at: None,
+                expires_at: None,
+            })
+            .collect()
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-6 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn axis_aligned_variances() {
+        // Variance 2 along x, 0.5 along y, none along z
+        let data = records(&[[2.0, 0.0, 5.0], [-2.0, 0.0, 5.0], [0.0, 1.0, 5.0], [0.0, -1.0, 5.0]]);
+        let (variances, total) = principal_variances(&data, 3);
+        assert!(close(total, 2.5));
+        // The zero-variance direction is dropped
+        assert_eq!(variances.len(), 2);
+        assert!(close(variances[0], 2.0));
+        assert!(close(variances[1], 0.5));
+    }
+
+    #[test]
+    fn rotated_components_are_found() {
+        // All variance along the (1, 1, 0) diagonal
+        let data = records(&[[1.0, 1.0, 0.0], [-1.0, -1.0, 0.0], [3.0, 3.0, 0.0], [-3.0, -3.0, 0.0]]);
+        let (variances, total) = principal_variances(&data, 2);
+        assert!(close(total, 10.0));
+        assert_eq!(variances.len(), 1);
+        assert!(close(variances[0], 10.0));
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        assert_eq!(principal_variances(&[], 3), (Vec::new(), 0.0));
+        let (variances, total) = principal_variances(&records(&[[1.0, 2.0, 3.0]]), 3);
+        assert!(variances.is_empty());
+        assert_eq!(total, 0.0);
+    }
+
+    #[test]
+    fn n_components_caps_the_result() {
+        let data = records(&[[2.0, 0.0, 0.0], [-2.0, 0.0, 0.0], [0.0, 1.0, 0.5], [0.0, -1.0, -0.2]]);
+        assert_eq!(principal_variances(&data, 1).0.len(), 1);
+    }
+}
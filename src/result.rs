@@ -0,0 +1,473 @@
+//! Typed search results and the ranking pipeline behind `VectorStore.search`
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use vecstore::Neighbor;
+
+use crate::{compare_json, record_boost, scoring, VectorStore, OVERFETCH_FACTOR, SEQ_FIELD};
+
+/// How `VectorStore.search` turns ANN candidates into a page of results
+///
+/// `page` runs the stages below in order; each is a separate function so
+/// it can be tested on its own:
+///
+/// 1. `rescore`: boost factor, then the rerank bonus
+/// 2. `rank`: descending score, ties by insertion order or id
+/// 3. `dedup`: best result per `dedup_field` value
+/// 4. cut to `offset + k`, then `tie_break` by `tie_break_field`
+/// 5. `merge_pinned`: pinned results first, still `offset + k` in total
+/// 6. skip `offset`, then reorder the page by `sort_by`
+pub(crate) struct SearchOptions<'a> {
+    pub(crate) k: usize,
+    pub(crate) offset: usize,
+    pub(crate) apply_boost: bool,
+    /// Field whose value `use_rerank_field` adds to the score
+    pub(crate) rerank_field: Option<&'a str>,
+    pub(crate) stable_insertion_order: bool,
+    pub(crate) dedup_field: Option<&'a str>,
+    /// Field, descending flag and epsilon of `tie_break_field`
+    pub(crate) tie_break: Option<(&'a str, bool, f32)>,
+    /// Field and descending flag of `sort_by`
+    pub(crate) sort_by: Option<(&'a str, bool)>,
+}
+
+impl SearchOptions<'_> {
+    /// Results up to and including the requested page
+    pub(crate) fn wanted(&self) -> usize {
+        self.offset.saturating_add(self.k)
+    }
+
+    /// Candidates to fetch from the index, with headroom for the stages
+    /// that can reorder or drop them
+    pub(crate) fn fetch(&self) -> usize {
+        if self.apply_boost || self.rerank_field.is_some() || self.dedup_field.is_some() {
+            self.wanted().saturating_mul(OVERFETCH_FACTOR)
+        } else {
+            self.wanted()
+        }
+    }
+
+    /// Run every stage over the fetched candidates and the pinned results
+    /// (in pinning order, scored against the query), returning the page
+    pub(crate) fn page(&self, mut candidates: Vec<Neighbor>, mut pinned: Vec<Neighbor>) -> Vec<Neighbor> {
+        rescore(&mut candidates, self.apply_boost, self.rerank_field);
+        rescore(&mut pinned, self.apply_boost, self.rerank_field);
+        rank(&mut candidates, self.stable_insertion_order);
+        if let Some(field) = self.dedup_field {
+            dedup(&mut candidates, field);
+        }
+        candidates.truncate(self.wanted());
+        if let Some((field, desc, epsilon)) = self.tie_break {
+            tie_break(&mut candidates, field, desc, epsilon.max(0.0));
+        }
+        merge_pinned(&mut candidates, pinned, self.wanted());
+
+        let mut page: Vec<Neighbor> = candidates.into_iter().skip(self.offset).collect();
+        if let Some((field, desc)) = self.sort_by {
+            page.sort_by(|a, b| compare_field(a, b, field, desc));
+        }
+        page
+    }
+}
+
+/// Multiply scores by each record's boost, then add its rerank bonus
+///
+/// Records without a numeric `rerank_field` value get no bonus.
+fn rescore(results: &mut [Neighbor], apply_boost: bool, rerank_field: Option<&str>) {
+    for result in results {
+        if apply_boost {
+            result.score *= record_boost(&result.metadata);
+        }
+        if let Some(field) = rerank_field {
+            let bonus = result.metadata.fields.get(field).and_then(|v| v.as_f64());
+            result.score += bonus.unwrap_or(0.0) as f32;
+        }
+    }
+}
+
+/// Sort by descending score, breaking ties by id so that pages never
+/// overlap or skip a result
+///
+/// With `stable_insertion_order`, ties go by insertion sequence number
+/// first; records without one go last among their ties.
+fn rank(results: &mut [Neighbor], stable_insertion_order: bool) {
+    let seq = |r: &Neighbor| r.metadata.fields.get(SEQ_FIELD).and_then(|v| v.as_u64()).unwrap_or(u64::MAX);
+    results.sort_by(|a, b| {
+        let order = b.score.total_cmp(&a.score);
+        let order = if stable_insertion_order {
+            order.then_with(|| seq(a).cmp(&seq(b)))
+        } else {
+            order
+        };
+        order.then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+/// Keep only the first (best ranked) result per value of `field`;
+/// records missing the field are never merged
+fn dedup(results: &mut Vec<Neighbor>, field: &str) {
+    let mut seen = HashSet::new();
+    results.retain(|r| match r.metadata.fields.get(field) {
+        Some(value) => seen.insert(value.to_string()),
+        None => true,
+    });
+}
+
+/// Order two results by a metadata field, records missing it last
+fn compare_field(a: &Neighbor, b: &Neighbor, field: &str, desc: bool) -> Ordering {
+    match (a.metadata.fields.get(field), b.metadata.fields.get(field)) {
+        (Some(x), Some(y)) if desc => compare_json(y, x),
+        (Some(x), Some(y)) => compare_json(x, y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Reorder runs of near-tied results by a metadata field
+///
+/// `results` must be sorted by descending score. A run starts at a result
+/// and extends over the following results whose score is within `epsilon`
+/// of the run's first score. Within a run, results are ordered by `field`
+/// (descending if `desc`), records missing the field go last, and the
+/// existing order is kept for equal values.
+fn tie_break(results: &mut [Neighbor], field: &str, desc: bool, epsilon: f32) {
+    let mut start = 0;
+    while start < results.len() {
+        let top = results[start].score;
+        let end = start
+            + results[start..]
+                .iter()
+                .take_while(|r| top - r.score <= epsilon)
+                .count();
+
+        results[start..end].sort_by(|a, b| compare_field(a, b, field, desc));
+        start = end;
+    }
+}
+
+/// Put the pinned results first, drop their other occurrences and cut the
+/// list back to `wanted`
+fn merge_pinned(results: &mut Vec<Neighbor>, pinned: Vec<Neighbor>, wanted: usize) {
+    if pinned.is_empty() {
+        return;
+    }
+    let pinned_keys: HashSet<&str> = pinned.iter().map(|p| p.id.as_str()).collect();
+    results.retain(|r| !pinned_keys.contains(r.id.as_str()));
+    results.splice(0..0, pinned);
+    results.truncate(wanted);
+}
+
+/// A single search hit with typed attributes
+///
+/// Returned by `VectorStore.search_objects`. Attributes are read-only;
+/// `vector` is None unless the vectors were requested.
+#[pyclass(frozen, get_all, module = "tf_rust")]
+pub(crate) struct SearchResult {
+    pub(crate) id: Py<PyAny>,
+    pub(crate) score: f32,
+    pub(crate) title: String,
+    pub(crate) url: String,
+    pub(crate) summary: String,
+    pub(crate) vector: Option<Vec<f32>>,
+}
+
+#[pymethods]
+impl SearchResult {
+    /// Convert to a dict with the same keys as a `search` result
+    /// (plus "vector" when present)
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("id", &self.id)?;
+        dict.set_item("score", self.score)?;
+        dict.set_item("title", &self.title)?;
+        dict.set_item("url", &self.url)?;
+        dict.set_item("summary", &self.summary)?;
+        if let Some(vector) = &self.vector {
+            dict.set_item("vector", vector)?;
+        }
+        Ok(dict)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "SearchResult(id={}, score={:.4}, title={})",
+            self.id.bind(py).repr()?,
+            self.score,
+            self.title.as_str().into_pyobject(py)?.repr()?
+        ))
+    }
+}
+
+/// Lazy iterator over ranked search results
+///
+/// Returned by `VectorStore.search_iter`. The ranked candidates (ids, scores
+/// and metadata, no vectors) are fetched up front; a result dict is only
+/// built when the iterator is advanced.
+#[pyclass(module = "tf_rust")]
+pub(crate) struct SearchIter {
+    store: Py<VectorStore>,
+    results: std::vec::IntoIter<Neighbor>,
+}
+
+impl SearchIter {
+    pub(crate) fn new(store: Py<VectorStore>, results: Vec<Neighbor>) -> Self {
+        SearchIter {
+            store,
+            results: results.into_iter(),
+        }
+    }
+}
+
+#[pymethods]
+impl SearchIter {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(result) = self.results.next() else {
+            return Ok(None);
+        };
+        let store = self.store.try_borrow(py)?;
+        store.result_dict(py, &result.id, result.score, &result.metadata).map(Some)
+    }
+
+    /// Number of results not yet yielded
+    fn __len__(&self) -> usize {
+        self.results.len()
+    }
+}
+
+/// Cursor walking the whole store in descending similarity to a vector
+///
+/// Returned by `VectorStore.ranked_cursor`. The cursor only remembers the
+/// (score, id) of the last record it returned. Every `next_batch` scores
+/// the current active records exactly under the read lock and returns the
+/// next ones after that position, ordered by score (descending) then id.
+/// Records written later that rank after the position are picked up,
+/// those ranking before it are not, and deleted records simply vanish.
+#[pyclass(module = "tf_rust")]
+pub(crate) struct RankedCursor {
+    store: Py<VectorStore>,
+    query: Vec<f32>,
+    /// (score, id) of the last returned record
+    position: Option<(f32, String)>,
+    exhausted: bool,
+}
+
+impl RankedCursor {
+    pub(crate) fn new(store: Py<VectorStore>, query: Vec<f32>) -> Self {
+        RankedCursor {
+            store,
+            query,
+            position: None,
+            exhausted: false,
+        }
+    }
+}
+
+#[pymethods]
+impl RankedCursor {
+    /// Return the next `n` records
+    ///
+    /// Each call scores every active record, O(store size).
+    ///
+    /// Args:
+    ///     n: Maximum number of records to return
+    ///
+    /// Returns:
+    ///     List of result dicts (same shape as `search`); shorter than `n`
+    ///     once the store runs out, and empty after that
+    ///
+    /// Raises:
+    ///     ValueError: If n is 0
+    fn next_batch<'py>(&mut self, py: Python<'py>, n: usize) -> PyResult<Bound<'py, PyList>> {
+        if n == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("n must be at least 1"));
+        }
+        let batch = PyList::empty(py);
+        if self.exhausted {
+            return Ok(batch);
+        }
+
+        let store = self.store.try_borrow(py)?;
+        let records = store.read_store()?.list_active();
+        let (metric, query) = (store.metric, &self.query);
+        let scores = store.install(|| scoring::score_all(metric, &records, query));
+
+        // After the cursor means: lower score, or the same score and a larger id
+        let mut candidates: Vec<(f32, &vecstore::Record)> = scores
+            .into_iter()
+            .zip(&records)
+            .filter(|(score, record)| match &self.position {
+                None => true,
+                Some((last, id)) => score < last || (score == last && record.id > *id),
+            })
+            .collect();
+        let order = |a: &(f32, &vecstore::Record), b: &(f32, &vecstore::Record)| {
+            b.0.total_cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id))
+        };
+        if candidates.len() > n {
+            candidates.select_nth_unstable_by(n - 1, order);
+            candidates.truncate(n);
+        }
+        candidates.sort_by(order);
+
+        if candidates.len() < n {
+            self.exhausted = true;
+        }
+        if let Some((score, record)) = candidates.last() {
+            self.position = Some((*score, record.id.clone()));
+        }
+        for (score, record) in candidates {
+            batch.append(store.result_dict(py, &record.id, score, &record.metadata)?)?;
+        }
+        Ok(batch)
+    }
+
+    /// Whether the last batch came back short, i.e. nothing is left
+    #[getter]
+    fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use vecstore::Metadata;
+
+    /// A result with the given metadata fields (a JSON object)
+    fn hit(id: &str, score: f32, fields: Value) -> Neighbor {
+        let Value::Object(fields) = fields else {
+            panic!("fields must be an object");
+        };
+        Neighbor {
+            id: id.to_string(),
+            score,
+            metadata: Metadata {
+                fields: fields.into_iter().collect(),
+            },
+        }
+    }
+
+    fn ids(results: &[Neighbor]) -> Vec<&str> {
+        results.iter().map(|r| r.id.as_str()).collect()
+    }
+
+    fn options() -> SearchOptions<'static> {
+        SearchOptions {
+            k: 2,
+            offset: 0,
+            apply_boost: false,
+            rerank_field: None,
+            stable_insertion_order: false,
+            dedup_field: None,
+            tie_break: None,
+            sort_by: None,
+        }
+    }
+
+    #[test]
+    fn fetch_overfetches_only_for_reordering_stages() {
+        let plain = SearchOptions { offset: 3, ..options() };
+        assert_eq!((plain.wanted(), plain.fetch()), (5, 5));
+        let dedup = SearchOptions { dedup_field: Some("doc"), ..plain };
+        assert_eq!(dedup.fetch(), 5 * OVERFETCH_FACTOR);
+        let huge = SearchOptions { k: usize::MAX, apply_boost: true, ..options() };
+        assert_eq!(huge.fetch(), usize::MAX);
+    }
+
+    #[test]
+    fn rescore_boosts_before_adding_the_bonus() {
+        let mut results = vec![
+            hit("a", 0.5, json!({"boost": 2.0, "rr": 0.25})),
+            hit("b", 0.5, json!({"rr": "high"})),
+        ];
+        rescore(&mut results, true, Some("rr"));
+        assert_eq!(results[0].score, 1.25);
+        assert_eq!(results[1].score, 0.5);
+    }
+
+    #[test]
+    fn rank_breaks_ties_by_id_or_insertion_order() {
+        let mut results = vec![
+            hit("b", 0.5, json!({"_seq": 1})),
+            hit("c", 0.9, json!({})),
+            hit("a", 0.5, json!({"_seq": 2})),
+            hit("d", 0.5, json!({})),
+        ];
+        rank(&mut results, false);
+        assert_eq!(ids(&results), ["c", "a", "b", "d"]);
+        rank(&mut results, true);
+        assert_eq!(ids(&results), ["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn dedup_keeps_the_best_per_value() {
+        let mut results = vec![
+            hit("a1", 0.9, json!({"doc": "a"})),
+            hit("x", 0.8, json!({})),
+            hit("a2", 0.7, json!({"doc": "a"})),
+            hit("y", 0.6, json!({})),
+            hit("b1", 0.5, json!({"doc": "b"})),
+        ];
+        dedup(&mut results, "doc");
+        assert_eq!(ids(&results), ["a1", "x", "y", "b1"]);
+    }
+
+    #[test]
+    fn tie_break_reorders_only_within_runs() {
+        let mut results = vec![
+            hit("a", 0.9, json!({"year": 2001})),
+            hit("b", 0.89, json!({"year": 2020})),
+            hit("c", 0.89, json!({})),
+            hit("d", 0.5, json!({"year": 2030})),
+        ];
+        tie_break(&mut results, "year", true, 0.0);
+        assert_eq!(ids(&results), ["a", "b", "c", "d"]);
+        tie_break(&mut results, "year", true, 0.02);
+        assert_eq!(ids(&results), ["b", "a", "c", "d"]);
+    }
+
+    #[test]
+    fn merge_pinned_puts_pinned_first_without_repeats() {
+        let mut results = vec![hit("a", 0.9, json!({})), hit("b", 0.8, json!({})), hit("c", 0.7, json!({}))];
+        merge_pinned(&mut results, vec![hit("c", 0.7, json!({})), hit("z", 0.1, json!({}))], 3);
+        assert_eq!(ids(&results), ["c", "z", "a"]);
+    }
+
+    #[test]
+    fn page_applies_offset_after_dedup_and_pinning() {
+        let candidates = vec![
+            hit("a1", 0.9, json!({"doc": "a"})),
+            hit("a2", 0.85, json!({"doc": "a"})),
+            hit("b1", 0.8, json!({"doc": "b"})),
+            hit("c1", 0.7, json!({"doc": "c"})),
+            hit("d1", 0.6, json!({"doc": "d"})),
+        ];
+        let pinned = vec![hit("c1", 0.7, json!({"doc": "c"}))];
+        let first = SearchOptions { dedup_field: Some("doc"), ..options() };
+        let second = SearchOptions { offset: 2, ..first };
+
+        // Deduplicated: a1 b1 c1 d1; pinned c1 moves up and counts towards k
+        assert_eq!(ids(&first.page(candidates.clone(), pinned.clone())), ["c1", "a1"]);
+        assert_eq!(ids(&second.page(candidates, pinned)), ["b1", "d1"]);
+    }
+
+    #[test]
+    fn page_sort_by_only_reorders_the_page() {
+        let candidates = vec![
+            hit("a", 0.9, json!({"title": "z"})),
+            hit("b", 0.8, json!({})),
+            hit("c", 0.7, json!({"title": "m"})),
+            hit("d", 0.6, json!({"title": "a"})),
+        ];
+        let sorted = SearchOptions { k: 3, sort_by: Some(("title", false)), ..options() };
+        assert_eq!(ids(&sorted.page(candidates, Vec::new())), ["c", "a", "b"]);
+    }
+}
@@ -0,0 +1,219 @@
+//! Metadata field types declared with `VectorStore(schema=...)`
+//!
+//! A schema maps field names to the type their values must have:
+//!
+//! ```text
+//! {"url": str, "published_at": float, "tags": list}
+//! {"url": "str", "published_at": "float"}     type names work too
+//! ```
+//!
+//! Supported types are `str`, `int`, `float`, `bool`, `list` and `dict`.
+//! As in Python's numeric tower, an int is a valid `float`, but a bool is
+//! not an `int`. Internal bookkeeping fields (leading underscore) are never
+//! checked.
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+use serde_json::Value;
+use std::collections::HashMap;
+use vecstore::Metadata;
+
+use crate::{BOOST_FIELD, DOCUMENT_FIELDS, INTERNAL_PREFIX};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Str,
+    Int,
+    Float,
+    Bool,
+    List,
+    Dict,
+}
+
+impl FieldType {
+    fn parse(name: &str) -> PyResult<Self> {
+        Ok(match name {
+            "str" => FieldType::Str,
+            "int" => FieldType::Int,
+            "float" => FieldType::Float,
+            "bool" => FieldType::Bool,
+            "list" => FieldType::List,
+            "dict" => FieldType::Dict,
+            other => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "Unsupported schema type '{}'. Expected str, int, float, bool, list or dict",
+                    other
+                )));
+            }
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FieldType::Str => "str",
+            FieldType::Int => "int",
+            FieldType::Float => "float",
+            FieldType::Bool => "bool",
+            FieldType::List => "list",
+            FieldType::Dict => "dict",
+        }
+    }
+
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            FieldType::Str => value.is_string(),
+            FieldType::Int => value.is_i64() || value.is_u64(),
+            FieldType::Float => value.is_number(),
+            FieldType::Bool => value.is_boolean(),
+            FieldType::List => value.is_array(),
+            FieldType::Dict => value.is_object(),
+        }
+    }
+}
+
+/// Python-style name of a JSON value's type, for error messages
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "None",
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "int",
+        Value::String(_) => "str",
+        Value::Array(_) => "list",
+        Value::Object(_) => "dict",
+    }
+}
+
+/// Expected type per field, checked before metadata is written
+pub(crate) struct Schema {
+    fields: HashMap<String, FieldType>,
+    /// Reject fields the schema does not declare
+    strict: bool,
+}
+
+impl Schema {
+    /// Parse a `{field: type}` dict, where a type is a Python type or its name
+    pub(crate) fn parse(schema: &Bound<'_, PyDict>, strict: bool) -> PyResult<Self> {
+        let mut fields = HashMap::with_capacity(schema.len());
+        for (field, kind) in schema.iter() {
+            let field: String = field.extract()?;
+            let name = match kind.cast::<PyType>() {
+                Ok(kind) => kind.name()?.extract::<String>()?,
+                Err(_) => kind.extract::<String>()?,
+            };
+            fields.insert(field, FieldType::parse(&name)?);
+        }
+        Ok(Schema { fields, strict })
+    }
+
+    /// Check every public field of `metadata`
+    ///
+    /// With `strict`, fields outside the schema are rejected too, except
+    /// the document fields (title, url, summary) every record carries and
+    /// the `boost` field set by `update`.
+    pub(crate) fn check(&self, metadata: &Metadata) -> PyResult<()> {
+        for (field, value) in &metadata.fields {
+            if field.starts_with(INTERNAL_PREFIX) {
+                continue;
+            }
+            match self.fields.get(field) {
+                Some(kind) if !kind.accepts(value) => {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "Schema violation: field '{}' must be {}, got {}",
+                        field,
+                        kind.name(),
+                        type_name(value)
+                    )));
+                }
+                None if self.strict && !DOCUMENT_FIELDS.contains(&field.as_str()) && field != BOOST_FIELD => {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "Schema violation: field '{}' is not declared in the schema",
+                        field
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema(fields: &[(&str, FieldType)], strict: bool) -> Schema {
+        Schema {
+            fields: fields.iter().map(|&(f, t)| (f.to_string(), t)).collect(),
+            strict,
+        }
+    }
+
+    fn metadata(fields: Value) -> Metadata {
+        let Value::Object(map) = fields else { unreachable!() };
+        Metadata {
+            fields: map.into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn numeric_tower() {
+        assert!(FieldType::Float.accepts(&json!(1)));
+        assert!(FieldType::Float.accepts(&json!(1.5)));
+        assert!(FieldType::Int.accepts(&json!(u64::MAX)));
+        assert!(!FieldType::Int.accepts(&json!(1.5)));
+        assert!(!FieldType::Int.accepts(&json!(true)));
+        assert!(!FieldType::Bool.accepts(&json!(1)));
+    }
+
+    #[test]
+    fn type_names() {
+        for name in ["str", "int", "float", "bool", "list", "dict"] {
+            assert_eq!(FieldType::parse(name).unwrap().name(), name);
+        }
+        assert!(FieldType::parse("bytes").is_err());
+        assert_eq!(type_name(&json!(null)), "None");
+        assert_eq!(type_name(&json!(2)), "int");
+        assert_eq!(type_name(&json!(2.0)), "float");
+    }
+
+    #[test]
+    fn check_declared_fields() {
+        let s = schema(&[("year", FieldType::Int), ("tags", FieldType::List)], false);
+        assert!(s.check(&metadata(json!({"year": 2024, "tags": ["a"], "other": 1}))).is_ok());
+        assert!(s.check(&metadata(json!({"year": "2024"}))).is_err());
+        assert!(s.check(&metadata(json!({"tags": "a"}))).is_err());
+    }
+
+    #[test]
+    fn internal_fields_are_never_checked() {
+        let s = schema(&[("_seq", FieldType::Str)], true);
+        assert!(s.check(&metadata(json!({"_seq": 1, "_norm": 0.5}))).is_ok());
+    }
+
+    #[test]
+    fn strict_rejects_undeclared_fields() {
+        let s = schema(&[("year", FieldType::Int)], true);
+        let document = json!({"title": "t", "url": "u", "summary": "", "boost": 2.0, "year": 1});
+        assert!(s.check(&metadata(document)).is_ok());
+        assert!(s.check(&metadata(json!({"author": "a"}))).is_err());
+    }
+
+    #[test]
+    fn parse_accepts_types_and_names() {
+        Python::initialize();
+        Python::attach(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("a", py.get_type::<pyo3::types::PyFloat>()).unwrap();
+            dict.set_item("b", "list").unwrap();
+            let s = Schema::parse(&dict, false).unwrap();
+            assert!(s.fields["a"] == FieldType::Float);
+            assert!(s.fields["b"] == FieldType::List);
+
+            dict.set_item("c", "tuple").unwrap();
+            let err = Schema::parse(&dict, false).err().unwrap();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyValueError>(py));
+        });
+    }
+}
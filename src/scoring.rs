@@ -0,0 +1,365 @@
+//! Scoring kernels shared by the brute-force (exhaustive) code paths
+//!
+//! All scores use the same convention as `VectorStore.search`: higher means
+//! more similar. The HNSW backend reports raw distances for some metrics, so
+//! `from_backend_score` converts them onto the same scale.
+//!
+//! The inner loops accumulate into `LANES` independent partial sums over
+//! fixed-size chunks. Unlike a single running sum, which is a serial chain
+//! of float additions the compiler may not reorder, this maps directly onto
+//! packed SIMD registers (SSE/AVX on x86_64, NEON on aarch64) in safe,
+//! stable Rust; the scalar tail handles lengths that are not a multiple of
+//! `LANES`. Results can differ from a sequential sum in the last bits.
+
+use rayon::prelude::*;
+use vecstore::{Distance, Record};
+
+/// Number of partial sums kept by the kernels: two AVX or four SSE/NEON registers
+const LANES: usize = 8;
+
+/// Sum `term(x, y)` over the element pairs of `a` and `b`, `LANES` at a time
+///
+/// Like `zip`, extra elements of the longer slice are ignored.
+#[inline(always)]
+fn lane_sum(a: &[f32], b: &[f32], term: impl Fn(f32, f32) -> f32) -> f32 {
+    let n = a.len().min(b.len());
+    let (a_chunks, a_tail) = a[..n].as_chunks::<LANES>();
+    let (b_chunks, b_tail) = b[..n].as_chunks::<LANES>();
+
+    let mut acc = [0.0f32; LANES];
+    for (x, y) in a_chunks.iter().zip(b_chunks) {
+        for lane in 0..LANES {
+            acc[lane] += term(x[lane], y[lane]);
+        }
+    }
+    let tail: f32 = a_tail.iter().zip(b_tail).map(|(&x, &y)| term(x, y)).sum();
+    acc.iter().sum::<f32>() + tail
+}
+
+/// Plain dot product
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    lane_sum(a, b, |x, y| x * y)
+}
+
+/// L2 (Euclidean) norm
+pub(crate) fn norm(a: &[f32]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// Scale a vector to unit L2 norm in place; zero vectors are left unchanged
+pub(crate) fn normalize(a: &mut [f32]) {
+    let n = norm(a);
+    if n > 0.0 {
+        a.iter_mut().for_each(|x| *x /= n);
+    }
+}
+
+/// Euclidean distance
+pub(crate) fn l2(a: &[f32], b: &[f32]) -> f32 {
+    lane_sum(a, b, |x, y| (x - y) * (x - y)).sqrt()
+}
+
+/// Cosine similarity, 0.0 if either vector has zero length
+///
+/// The dot product and both norms are accumulated in a single pass.
+pub(crate) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    let (a_chunks, a_tail) = a[..n].as_chunks::<LANES>();
+    let (b_chunks, b_tail) = b[..n].as_chunks::<LANES>();
+
+    let mut ab = [0.0f32; LANES];
+    let mut aa = [0.0f32; LANES];
+    let mut bb = [0.0f32; LANES];
+    for (x, y) in a_chunks.iter().zip(b_chunks) {
+        for lane in 0..LANES {
+            ab[lane] += x[lane] * y[lane];
+            aa[lane] += x[lane] * x[lane];
+            bb[lane] += y[lane] * y[lane];
+        }
+    }
+    let (mut dot, mut a_sq, mut b_sq) = (ab.iter().sum::<f32>(), aa.iter().sum::<f32>(), bb.iter().sum::<f32>());
+    for (&x, &y) in a_tail.iter().zip(b_tail) {
+        dot += x * y;
+        a_sq += x * x;
+        b_sq += y * y;
+    }
+
+    let denom = a_sq.sqrt() * b_sq.sqrt();
+    if denom > 0.0 { dot / denom } else { 0.0 }
+}
+
+/// Similarity between two vectors under the store metric (higher = more similar)
+///
+/// Euclidean uses `1 / (1 + distance)`, the same inversion vecstore applies.
+pub(crate) fn similarity(metric: Distance, a: &[f32], b: &[f32]) -> f32 {
+    match metric {
+        Distance::DotProduct => dot(a, b),
+        Distance::Euclidean => 1.0 / (1.0 + l2(a, b)),
+        _ => cosine(a, b),
+    }
+}
+
+/// Metadata key caching a record's L2 norm, see `VectorStore.precompute_norms`
+pub(crate) const NORM_FIELD: &str = "_norm";
+
+/// L2 norm of a record's vector, read from the cache when present
+pub(crate) fn record_norm(record: &Record) -> f32 {
+    record
+        .metadata
+        .fields
+        .get(NORM_FIELD)
+        .and_then(|v| v.as_f64())
+        .map_or_else(|| norm(&record.vector), |n| n as f32)
+}
+
+/// `similarity` against a stored record, with the query norm computed once
+/// by the caller and the record norm taken from the cache when present
+fn record_similarity(metric: Distance, query: &[f32], query_norm: f32, record: &Record) -> f32 {
+    match metric {
+        Distance::DotProduct | Distance::Euclidean => similarity(metric, query, &record.vector),
+        _ => {
+            let denom = query_norm * record_norm(record);
+            if denom > 0.0 { dot(query, &record.vector) / denom } else { 0.0 }
+        }
+    }
+}
+
+/// Convert a score reported by the HNSW backend into a similarity
+///
+/// For Cosine and DotProduct the backend reports `1 - similarity`.
+pub(crate) fn from_backend_score(metric: Distance, raw: f32) -> f32 {
+    match metric {
+        Distance::Cosine | Distance::DotProduct => 1.0 - raw,
+        _ => raw,
+    }
+}
+
+/// Similarity of every row vector to every column vector, row-major
+/// `rows.len() x cols.len()`
+///
+/// Rows are scored in parallel on the current rayon pool.
+pub(crate) fn cross(metric: Distance, rows: &[&[f32]], cols: &[&[f32]]) -> Vec<f32> {
+    rows.par_iter()
+        .flat_map_iter(|row| cols.iter().map(move |col| similarity(metric, row, col)))
+        .collect()
+}
+
+/// Similarity of the query to every record, in record order
+///
+/// Scoring runs on the current rayon pool.
+pub(crate) fn score_all(metric: Distance, records: &[Record], query: &[f32]) -> Vec<f32> {
+    let query_norm = norm(query);
+    records
+        .par_iter()
+        .map(|r| record_similarity(metric, query, query_norm, r))
+        .collect()
+}
+
+/// Convert a similarity into the metric's distance (smaller = more similar)
+///
+/// Cosine: `1 - similarity`. DotProduct: `-dot`. Euclidean: the L2
+/// distance, undoing the `1 / (1 + distance)` inversion.
+pub(crate) fn to_distance(metric: Distance, similarity: f32) -> f32 {
+    match metric {
+        Distance::DotProduct => -similarity,
+        Distance::Euclidean => 1.0 / similarity - 1.0,
+        _ => 1.0 - similarity,
+    }
+}
+
+/// Component-wise mean of the records' vectors, None for no records
+pub(crate) fn centroid(records: &[Record]) -> Option<Vec<f32>> {
+    let first = records.first()?;
+    let mut sum = vec![0.0f64; first.vector.len()];
+    for record in records {
+        for (s, &x) in sum.iter_mut().zip(&record.vector) {
+            *s += x as f64;
+        }
+    }
+    let n = records.len() as f64;
+    Some(sum.into_iter().map(|s| (s / n) as f32).collect())
+}
+
+/// Exact top-k over a set of records, sorted by descending similarity
+pub(crate) fn top_k<'a>(
+    metric: Distance,
+    records: &'a [Record],
+    query: &[f32],
+    k: usize,
+) -> Vec<(&'a Record, f32)> {
+    let mut scored: Vec<(&Record, f32)> = records.iter().zip(score_all(metric, records, query)).collect();
+
+    let k = k.min(scored.len());
+    if k == 0 {
+        return Vec::new();
+    }
+    if k < scored.len() {
+        scored.select_nth_unstable_by(k - 1, |a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+    }
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored
+}
+
+/// Similarity of every pair of vectors as a row-major `n x n` matrix
+///
+/// The matrix is symmetric, so each pair is scored once.
+pub(crate) fn pairwise(metric: Distance, vectors: &[&[f32]]) -> Vec<f32> {
+    let n = vectors.len();
+    let mut matrix = vec![0.0; n * n];
+    for i in 0..n {
+        for j in i..n {
+            let score = similarity(metric, vectors[i], vectors[j]);
+            matrix[i * n + j] = score;
+            matrix[j * n + i] = score;
+        }
+    }
+    matrix
+}
+
+/// Greedy maximal marginal relevance selection
+///
+/// `relevance[i]` is candidate i's similarity to the query. Returns up to k
+/// candidate indices in selection order.
+pub(crate) fn mmr(metric: Distance, relevance: &[f32], vectors: &[&[f32]], k: usize, lambda: f32) -> Vec<usize> {
+    let mut selected: Vec<usize> = Vec::with_capacity(k.min(vectors.len()));
+    // Highest similarity of each candidate to anything selected so far
+    let mut redundancy = vec![f32::NEG_INFINITY; vectors.len()];
+    let mut available = vec![true; vectors.len()];
+
+    while selected.len() < k {
+        let best = (0..vectors.len()).filter(|&i| available[i]).max_by(|&a, &b| {
+            let objective = |i: usize| {
+                let penalty = if selected.is_empty() { 0.0 } else { redundancy[i] };
+                lambda * relevance[i] - (1.0 - lambda) * penalty
+            };
+            // Prefer the earlier (more relevant) candidate on ties
+            objective(a).total_cmp(&objective(b)).then(b.cmp(&a))
+        });
+        let Some(best) = best else { break };
+
+        available[best] = false;
+        selected.push(best);
+        for i in 0..vectors.len() {
+            if available[i] {
+                redundancy[i] = redundancy[i].max(similarity(metric, vectors[i], vectors[best]));
+            }
+        }
+    }
+
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use vecstore::Metadata;
+
+    fn record(id: &str, vector: &[f32]) -> Record {
+        Record {
+            id: id.to_string(),
+            vector: vector.to_vec(),
+            metadata: Metadata { fields: HashMap::new() },
+            created_at: 0,
+            deleted: false,
+            deleted_at: None,
+            expires_at: None,
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-4 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    #[test]
+    fn kernels_match_sequential_sums() {
+        // Lengths around LANES exercise both the chunked part and the tail
+        for n in [0, 1, 7, 8, 9, 17, 100] {
+            let a: Vec<f32> = (0..n).map(|i| (i as f32 * 0.37).sin()).collect();
+            let b: Vec<f32> = (0..n).map(|i| (i as f32 * 0.11).cos()).collect();
+            let dot_ref: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+            let l2_ref = a.iter().zip(&b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>().sqrt();
+            assert!(close(dot(&a, &b), dot_ref), "dot, n = {}", n);
+            assert!(close(l2(&a, &b), l2_ref), "l2, n = {}", n);
+        }
+    }
+
+    #[test]
+    fn cosine_of_zero_vector_is_zero() {
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert!(close(cosine(&[2.0, 0.0], &[3.0, 0.0]), 1.0));
+        assert!(close(cosine(&[1.0, 0.0], &[-1.0, 0.0]), -1.0));
+    }
+
+    #[test]
+    fn normalize_leaves_zero_vectors_alone() {
+        let mut v = [3.0, 4.0];
+        normalize(&mut v);
+        assert!(close(v[0], 0.6) && close(v[1], 0.8));
+        let mut zero = [0.0, 0.0];
+        normalize(&mut zero);
+        assert_eq!(zero, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn to_distance_inverts_similarity() {
+        let (a, b) = ([1.0, 2.0, 2.0], [0.0, 1.0, 0.0]);
+        assert!(close(to_distance(Distance::Euclidean, similarity(Distance::Euclidean, &a, &b)), l2(&a, &b)));
+        assert!(close(to_distance(Distance::Cosine, similarity(Distance::Cosine, &a, &b)), 1.0 - cosine(&a, &b)));
+        assert!(close(to_distance(Distance::DotProduct, similarity(Distance::DotProduct, &a, &b)), -2.0));
+    }
+
+    #[test]
+    fn record_norm_prefers_the_cache() {
+        let mut r = record("a", &[3.0, 4.0]);
+        assert!(close(record_norm(&r), 5.0));
+        r.metadata.fields.insert(NORM_FIELD.to_string(), json!(10.0));
+        assert!(close(record_norm(&r), 10.0));
+    }
+
+    #[test]
+    fn top_k_is_sorted_and_truncated() {
+        let records = [
+            record("a", &[1.0, 0.0]),
+            record("b", &[0.0, 1.0]),
+            record("c", &[0.7, 0.7]),
+        ];
+        let hits = top_k(Distance::Cosine, &records, &[1.0, 0.1], 2);
+        let ids: Vec<&str> = hits.iter().map(|(r, _)| r.id.as_str()).collect();
+        assert_eq!(ids, ["a", "c"]);
+        assert!(hits[0].1 >= hits[1].1);
+        assert!(top_k(Distance::Cosine, &records, &[1.0, 0.0], 0).is_empty());
+        assert_eq!(top_k(Distance::Cosine, &records, &[1.0, 0.0], 10).len(), 3);
+    }
+
+    #[test]
+    fn matrices_have_the_expected_layout() {
+        let a: &[f32] = &[1.0, 0.0];
+        let b: &[f32] = &[0.0, 2.0];
+        let m = cross(Distance::DotProduct, &[a, b], &[a, b, a]);
+        assert_eq!(m, [1.0, 0.0, 1.0, 0.0, 4.0, 0.0]);
+        let p = pairwise(Distance::DotProduct, &[a, b]);
+        assert_eq!(p, [1.0, 0.0, 0.0, 4.0]);
+    }
+
+    #[test]
+    fn centroid_is_the_mean() {
+        assert!(centroid(&[]).is_none());
+        let c = centroid(&[record("a", &[1.0, 2.0]), record("b", &[3.0, 6.0])]).unwrap();
+        assert_eq!(c, [2.0, 4.0]);
+    }
+
+    #[test]
+    fn mmr_trades_relevance_for_diversity() {
+        let near: &[f32] = &[1.0, 0.0];
+        let duplicate: &[f32] = &[1.0, 0.01];
+        let other: &[f32] = &[0.0, 1.0];
+        let relevance = [1.0, 0.99, 0.5];
+        let vectors = [near, duplicate, other];
+        assert_eq!(mmr(Distance::Cosine, &relevance, &vectors, 2, 1.0), [0, 1]);
+        assert_eq!(mmr(Distance::Cosine, &relevance, &vectors, 2, 0.5), [0, 2]);
+        assert_eq!(mmr(Distance::Cosine, &relevance, &vectors, 5, 0.5).len(), 3);
+    }
+}
@@ -0,0 +1,153 @@
+//! Sparse vectors (e.g. SPLADE term weights) attached to records
+//!
+//! A sparse vector is kept in the record's metadata as two parallel arrays
+//! sorted by index, in internal fields hidden from result dicts:
+//!
+//! ```text
+//! {"_sparse_idx": [3, 17, 2048], "_sparse_val": [0.4, 1.2, 0.7]}
+//! ```
+//!
+//! Scoring is the sparse dot product, computed by merging the two sorted
+//! index lists. There is no inverted index: a sparse query scores every
+//! active record, O(n * nnz).
+
+use pyo3::prelude::*;
+use serde_json::json;
+use vecstore::Metadata;
+
+const INDICES_FIELD: &str = "_sparse_idx";
+const VALUES_FIELD: &str = "_sparse_val";
+
+/// Non-zero entries sorted by strictly increasing index
+pub(crate) struct SparseVector {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+impl SparseVector {
+    /// Validate parallel index/value lists, sorting them by index
+    pub(crate) fn parse(indices: Vec<u32>, values: Vec<f32>) -> PyResult<Self> {
+        if indices.len() != values.len() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Sparse vector has {} indices but {} values",
+                indices.len(),
+                values.len()
+            )));
+        }
+        if let Some(value) = values.iter().find(|v| !v.is_finite()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Sparse vector values must be finite, got {}",
+                value
+            )));
+        }
+
+        let mut entries: Vec<(u32, f32)> = indices.into_iter().zip(values).collect();
+        entries.sort_unstable_by_key(|&(index, _)| index);
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Duplicate sparse index {}",
+                pair[0].0
+            )));
+        }
+        let (indices, values) = entries.into_iter().unzip();
+        Ok(SparseVector { indices, values })
+    }
+
+    /// Read the sparse vector stored in `metadata`, if any
+    pub(crate) fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let indices = metadata.fields.get(INDICES_FIELD)?.as_array()?;
+        let values = metadata.fields.get(VALUES_FIELD)?.as_array()?;
+        Some(SparseVector {
+            indices: indices.iter().map(|i| i.as_u64().unwrap_or(0) as u32).collect(),
+            values: values.iter().map(|v| v.as_f64().unwrap_or(0.0) as f32).collect(),
+        })
+    }
+
+    /// Store this vector in `metadata`; an empty vector removes it
+    pub(crate) fn write_to(&self, metadata: &mut Metadata) {
+        if self.indices.is_empty() {
+            metadata.fields.remove(INDICES_FIELD);
+            metadata.fields.remove(VALUES_FIELD);
+        } else {
+            metadata.fields.insert(INDICES_FIELD.to_string(), json!(self.indices));
+            metadata.fields.insert(VALUES_FIELD.to_string(), json!(self.values));
+        }
+    }
+
+    /// Dot product, or None when the vectors share no index
+    pub(crate) fn dot(&self, other: &SparseVector) -> Option<f32> {
+        let (mut i, mut j) = (0, 0);
+        let mut sum = 0.0;
+        let mut overlap = false;
+        while i < self.indices.len() && j < other.indices.len() {
+            match self.indices[i].cmp(&other.indices[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    sum += self.values[i] * other.values[j];
+                    overlap = true;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        overlap.then_some(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn sparse(indices: &[u32], values: &[f32]) -> SparseVector {
+        SparseVector::parse(indices.to_vec(), values.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn parse_sorts_by_index() {
+        let v = sparse(&[9, 1, 4], &[0.9, 0.1, 0.4]);
+        assert_eq!(v.indices, [1, 4, 9]);
+        assert_eq!(v.values, [0.1, 0.4, 0.9]);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_input() {
+        assert!(SparseVector::parse(vec![1, 2], vec![1.0]).is_err());
+        assert!(SparseVector::parse(vec![3, 1, 3], vec![1.0, 1.0, 1.0]).is_err());
+        assert!(SparseVector::parse(vec![1], vec![f32::NAN]).is_err());
+        assert!(SparseVector::parse(vec![1], vec![f32::INFINITY]).is_err());
+    }
+
+    #[test]
+    fn dot_sums_shared_indices_only() {
+        let a = sparse(&[1, 5, 9], &[1.0, 2.0, 3.0]);
+        let b = sparse(&[0, 5, 9, 12], &[7.0, 0.5, 2.0, 1.0]);
+        assert_eq!(a.dot(&b), Some(7.0));
+        assert_eq!(b.dot(&a), Some(7.0));
+    }
+
+    #[test]
+    fn dot_without_overlap_is_none() {
+        let a = sparse(&[1, 3], &[1.0, 1.0]);
+        let b = sparse(&[2, 4], &[1.0, 1.0]);
+        assert_eq!(a.dot(&b), None);
+        // A shared index with a zero product still counts as a match
+        let c = sparse(&[3], &[0.0]);
+        assert_eq!(a.dot(&c), Some(0.0));
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let mut metadata = Metadata { fields: HashMap::new() };
+        assert!(SparseVector::from_metadata(&metadata).is_none());
+
+        sparse(&[7, 2], &[0.25, 1.5]).write_to(&mut metadata);
+        let read = SparseVector::from_metadata(&metadata).unwrap();
+        assert_eq!(read.indices, [2, 7]);
+        assert_eq!(read.values, [1.5, 0.25]);
+
+        sparse(&[], &[]).write_to(&mut metadata);
+        assert!(metadata.fields.is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+//! Module-level similarity functions for vectors that are not in a store
+//!
+//! They use the same kernels as `VectorStore`, so a score computed here is
+//! directly comparable with `search` and `similarity` results.
+
+use pyo3::prelude::*;
+
+use crate::scoring;
+
+/// Reject vector pairs of different lengths
+fn check_lengths(a: &[f32], b: &[f32]) -> PyResult<()> {
+    if a.len() != b.len() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Vector length mismatch: {} vs {}",
+            a.len(),
+            b.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Cosine similarity of two vectors, 0.0 if either has zero length
+///
+/// Raises:
+///     ValueError: If the vectors have different lengths
+#[pyfunction]
+pub(crate) fn cosine(a: Vec<f32>, b: Vec<f32>) -> PyResult<f32> {
+    check_lengths(&a, &b)?;
+    Ok(scoring::cosine(&a, &b))
+}
+
+/// Dot product of two vectors
+///
+/// Raises:
+///     ValueError: If the vectors have different lengths
+#[pyfunction]
+pub(crate) fn dot(a: Vec<f32>, b: Vec<f32>) -> PyResult<f32> {
+    check_lengths(&a, &b)?;
+    Ok(scoring::dot(&a, &b))
+}
+
+/// Euclidean (L2) distance between two vectors
+///
+/// This is a distance (smaller = more similar). A Euclidean store reports
+/// `1 / (1 + l2(a, b))` as its similarity score.
+///
+/// Raises:
+///     ValueError: If the vectors have different lengths
+#[pyfunction]
+pub(crate) fn l2(a: Vec<f32>, b: Vec<f32>) -> PyResult<f32> {
+    check_lengths(&a, &b)?;
+    Ok(scoring::l2(&a, &b))
+}
@@ -0,0 +1,274 @@
+//! Write-ahead log for persistent stores opened with `wal=True`
+//!
+//! VecStore only reaches disk on `save()`, so a crash loses every change
+//! since the last save. With the log enabled, each mutation appends the
+//! resulting state of the touched records to `wal.log` in the store
+//! directory, one JSON object per line, and syncs it before the mutating
+//! method returns:
+//!
+//! ```text
+//! {"op":"upsert","id":"a","vector":[...],"metadata":{"fields":{...}}}
+//! {"op":"update","id":"a","metadata":{"fields":{...}}}
+//! {"op":"delete","id":"a"}
+//! {"op":"soft_delete","id":"a"}
+//! {"op":"undelete","id":"a"}
+//! ```
+//!
+//! Entries carry full record state rather than the call arguments, so
+//! replaying the log over a store that already contains some of them is
+//! idempotent. `save()`/`checkpoint()` truncate the log; a non-empty log
+//! found on `open` means the store was not saved after its last changes,
+//! and it is replayed.
+
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use vecstore::{Metadata, VecStore};
+
+const WAL_FILE: &str = "wal.log";
+
+/// One logged mutation
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(crate) enum WalEntry {
+    Upsert { id: String, vector: Vec<f32>, metadata: Metadata },
+    Update { id: String, metadata: Metadata },
+    Delete { id: String },
+    SoftDelete { id: String },
+    Undelete { id: String },
+}
+
+fn io_error(what: &str, path: &Path, e: impl std::fmt::Display) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("{} {}: {}", what, path.display(), e))
+}
+
+/// Append-only log file of a store directory
+pub(crate) struct Wal {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl Wal {
+    /// Open (or create) the log of the store at `root` for appending
+    pub(crate) fn open(root: &Path) -> PyResult<Self> {
+        let path = root.join(WAL_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| io_error("Failed to open write-ahead log", &path, e))?;
+        Ok(Wal {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Append entries and sync them to disk
+    pub(crate) fn append(&self, entries: &[WalEntry]) -> PyResult<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut buffer = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut buffer, entry)
+                .map_err(|e| io_error("Failed to encode write-ahead log entry for", &self.path, e))?;
+            buffer.push(b'\n');
+        }
+
+        let file = self
+            .file
+            .lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+        let mut file = &*file;
+        file.write_all(&buffer)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| io_error("Failed to append to write-ahead log", &self.path, e))
+    }
+
+    /// Drop every entry, after the store has been saved
+    pub(crate) fn truncate(&self) -> PyResult<()> {
+        let file = self
+            .file
+            .lock()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Lock error: {}", e)))?;
+        file.set_len(0)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| io_error("Failed to truncate write-ahead log", &self.path, e))
+    }
+}
+
+/// Apply the log left in `root` to `store`
+///
+/// A final line that does not parse is a write cut short by the crash and
+/// is skipped with a warning; an unparsable line before it is an error.
+///
+/// Returns:
+///     Number of replayed entries, 0 when there is no log
+pub(crate) fn replay(root: &Path, store: &mut VecStore) -> PyResult<usize> {
+    let path = root.join(WAL_FILE);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(io_error("Failed to read write-ahead log", &path, e)),
+    };
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| io_error("Failed to read write-ahead log", &path, e))?;
+
+    let mut replayed = 0;
+    for (number, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: WalEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(e) if number + 1 == lines.len() => {
+                log::warn!("Skipping truncated last entry of {}: {}", path.display(), e);
+                break;
+            }
+            Err(e) => return Err(io_error(&format!("Corrupted entry on line {} of", number + 1), &path, e)),
+        };
+
+        // Records that no longer exist are skipped, like the original call would have failed
+        let result = match entry {
+            WalEntry::Upsert { id, vector, metadata } => store.upsert(id, vector, metadata),
+            WalEntry::Update { id, metadata } => store.update_metadata(&id, metadata).or(Ok(())),
+            WalEntry::Delete { id } => store.remove(&id).or(Ok(())),
+            WalEntry::SoftDelete { id } => store.soft_delete(&id).map(|_| ()),
+            WalEntry::Undelete { id } => store.restore(&id).map(|_| ()),
+        };
+        result.map_err(|e| io_error(&format!("Failed to replay line {} of", number + 1), &path, e))?;
+        replayed += 1;
+    }
+
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Empty directory removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("tf_wal_test_{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn metadata(title: &str) -> Metadata {
+        Metadata {
+            fields: HashMap::from([("title".to_string(), json!(title))]),
+        }
+    }
+
+    fn upsert(id: &str, vector: &[f32], title: &str) -> WalEntry {
+        WalEntry::Upsert {
+            id: id.to_string(),
+            vector: vector.to_vec(),
+            metadata: metadata(title),
+        }
+    }
+
+    fn active(store: &VecStore) -> HashMap<String, (Vec<f32>, serde_json::Value)> {
+        store
+            .list_active()
+            .into_iter()
+            .map(|r| (r.id, (r.vector, r.metadata.fields["title"].clone())))
+            .collect()
+    }
+
+    #[test]
+    fn replay_without_log_is_a_no_op() {
+        let dir = TempDir::new();
+        let mut store = VecStore::open(dir.0.join("store")).unwrap();
+        assert_eq!(replay(&dir.0, &mut store).unwrap(), 0);
+    }
+
+    #[test]
+    fn replay_applies_entries_in_order() {
+        let dir = TempDir::new();
+        let wal = Wal::open(&dir.0).unwrap();
+        wal.append(&[
+            upsert("a", &[1.0, 0.0], "first"),
+            upsert("b", &[0.0, 1.0], "b"),
+            upsert("c", &[1.0, 1.0], "c"),
+        ])
+        .unwrap();
+        wal.append(&[
+            WalEntry::Update {
+                id: "a".to_string(),
+                metadata: metadata("second"),
+            },
+            WalEntry::Delete { id: "b".to_string() },
+            WalEntry::SoftDelete { id: "c".to_string() },
+            // Entries for records that are gone are skipped, not errors
+            WalEntry::Update {
+                id: "missing".to_string(),
+                metadata: metadata("x"),
+            },
+        ])
+        .unwrap();
+
+        let mut store = VecStore::open(dir.0.join("store")).unwrap();
+        assert_eq!(replay(&dir.0, &mut store).unwrap(), 7);
+        let records = active(&store);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records["a"], (vec![1.0, 0.0], json!("second")));
+        assert_eq!(store.list_deleted().len(), 1);
+
+        // Replaying the same log again converges on the same state
+        assert_eq!(replay(&dir.0, &mut store).unwrap(), 7);
+        assert_eq!(active(&store), records);
+    }
+
+    #[test]
+    fn replay_skips_a_truncated_last_line() {
+        let dir = TempDir::new();
+        let wal = Wal::open(&dir.0).unwrap();
+        wal.append(&[upsert("a", &[1.0, 0.0], "a")]).unwrap();
+        // A crash in the middle of the next write
+        let mut file = OpenOptions::new().append(true).open(dir.0.join(WAL_FILE)).unwrap();
+        file.write_all(br#"{"op":"upsert","id":"b","vec"#).unwrap();
+
+        let mut store = VecStore::open(dir.0.join("store")).unwrap();
+        assert_eq!(replay(&dir.0, &mut store).unwrap(), 1);
+        assert_eq!(active(&store).len(), 1);
+    }
+
+    #[test]
+    fn replay_rejects_corruption_before_the_last_line() {
+        let dir = TempDir::new();
+        std::fs::write(dir.0.join(WAL_FILE), "not json\n{\"op\":\"delete\",\"id\":\"a\"}\n").unwrap();
+        let mut store = VecStore::open(dir.0.join("store")).unwrap();
+        assert!(replay(&dir.0, &mut store).is_err());
+    }
+
+    #[test]
+    fn truncate_empties_the_log() {
+        let dir = TempDir::new();
+        let wal = Wal::open(&dir.0).unwrap();
+        wal.append(&[upsert("a", &[1.0, 0.0], "a")]).unwrap();
+        wal.truncate().unwrap();
+        wal.append(&[upsert("b", &[0.0, 1.0], "b")]).unwrap();
+
+        let mut store = VecStore::open(dir.0.join("store")).unwrap();
+        assert_eq!(replay(&dir.0, &mut store).unwrap(), 1);
+        assert!(active(&store).contains_key("b"));
+    }
+}
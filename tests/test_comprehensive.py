@@ -14,6 +14,7 @@ Uses shared DocumentStore instance to avoid lock contention.
 
 import pytest
 import threading
+import time
 from typing import List, Dict
 from tf import DocumentStore, SearchResult, TextEmbedder
 
@@ -451,6 +452,38 @@ class TestThreadSafety:
             metadata = shared_store.get(f"concurrent_add_{i}")
             assert metadata is not None
 
+    def test_writes_during_gil_free_scan(self):
+        """Test writes wait for a scan that released the GIL instead of failing."""
+        rust = pytest.importorskip("tf_rust")
+        store = rust.VectorStore(8)
+        for i in range(2000):
+            store.set_vector(f"doc_{i}", [float((i * j) % 7) for j in range(1, 9)], "title", "url")
+
+        errors = []
+        done = threading.Event()
+
+        def scan_worker():
+            try:
+                store.knn_graph(5)
+            except Exception as e:
+                errors.append(e)
+            done.set()
+
+        thread = threading.Thread(target=scan_worker)
+        thread.start()
+        written = 0
+        while not done.is_set():
+            try:
+                store.set_vector(f"new_{written}", [1.0] * 8, "title", "url")
+            except Exception as e:
+                errors.append(e)
+                break
+            written += 1
+        thread.join()
+
+        assert errors == []
+        assert store.len() == 2000 + written
+
 
 class TestErrorHandling:
     """Test error handling."""
@@ -523,6 +556,460 @@ class TestMemoryEfficiency:
         assert count <= 10
 
 
+
+@pytest.fixture
+def rust():
+    """The compiled extension module."""
+    return pytest.importorskip("tf_rust")
+
+
+class TestIntegerRoundTrip:
+    """Test that integer ids and metadata survive the Rust layer exactly."""
+
+    # Around 2^53, where a float64 round trip starts losing integers
+    LARGE_INTS = [2**53 - 1, 2**53, 2**53 + 1, 2**63 - 1, -(2**63), 2**64 - 1]
+
+    def test_int_ids_near_2_53(self, rust):
+        """Test int ids come back from get and search unchanged."""
+        store = rust.VectorStore(2, id_type="int")
+        ids = [i for i in self.LARGE_INTS if i < 2**63]
+        for doc_id in ids:
+            store.set_vector(doc_id, [1.0, 0.0], "title", "url")
+
+        assert store.len() == len(ids)
+        for doc_id in ids:
+            assert store.get(doc_id) is not None
+        found = [r["id"] for r in store.search([1.0, 0.0], k=len(ids))]
+        assert sorted(found) == sorted(ids)
+        assert all(type(i) is int for i in found)
+        # 2^53 and 2^53 + 1 collapse to the same float; they must stay apart
+        assert 2**53 in found and 2**53 + 1 in found
+
+    def test_int_ids_reject_out_of_range(self, rust):
+        """Test ids outside i64 are rejected instead of wrapped."""
+        store = rust.VectorStore(2, id_type="int")
+        with pytest.raises(TypeError):
+            store.set_vector(2**64, [1.0, 0.0], "title", "url")
+
+    def test_int_ids_reject_bool(self, rust):
+        """Test bool ids are rejected instead of stored as 0 or 1."""
+        store = rust.VectorStore(2, id_type="int")
+        with pytest.raises(TypeError):
+            store.set_vector(True, [1.0, 0.0], "title", "url")
+        assert store.len() == 0
+
+    def test_int_metadata_near_2_53(self, rust):
+        """Test int metadata values read back as the same Python int."""
+        store = rust.VectorStore(2)
+        store.set_vector("doc", [1.0, 0.0], "title", "url")
+        fields = {f"n{i}": value for i, value in enumerate(self.LARGE_INTS)}
+        fields["ratio"] = 0.5
+        store.set_metadata_batch([{"id": "doc", "fields": fields}])
+
+        record = store.to_list()[0]
+        for name, value in fields.items():
+            assert record[name] == value
+            assert type(record[name]) is type(value)
+
+    def test_int_metadata_survives_save_and_open(self, rust, tmp_path):
+        """Test large ints are exact after a save/open cycle."""
+        path = str(tmp_path / "store")
+        store = rust.VectorStore.open(path, dimension=2)
+        store.set_vector("doc", [1.0, 0.0], "title", "url")
+        store.set_metadata_batch([{"id": "doc", "fields": {"big": 2**53 + 1, "huge": 2**64 - 1}}])
+        store.save()
+        store.close()
+
+        reopened = rust.VectorStore.open(path)
+        record = reopened.to_list()[0]
+        assert record["big"] == 2**53 + 1
+        assert record["huge"] == 2**64 - 1
+
+    def test_int_metadata_rejects_out_of_range(self, rust):
+        """Test ints beyond 64 bits raise instead of losing precision."""
+        store = rust.VectorStore(2)
+        store.set_vector("doc", [1.0, 0.0], "title", "url")
+        with pytest.raises(ValueError):
+            store.set_metadata_batch([{"id": "doc", "fields": {"n": 2**64}}])
+
+
+class TestWriteAheadLog:
+    """Test that unsaved writes survive a crash when the log is enabled."""
+
+    def write_unsaved(self, rust, path):
+        """Write a few records with the log enabled, then drop the store unsaved."""
+        store = rust.VectorStore.open(path, dimension=2, wal=True)
+        store.set_vector("a", [1.0, 0.0], "Title A", "url-a")
+        store.set_vector("b", [0.0, 1.0], "Title B", "url-b")
+        store.set_vector("c", [1.0, 1.0], "Title C", "url-c")
+        store.rm("c")
+        del store
+
+    def test_reopen_replays_and_truncates(self, rust, tmp_path):
+        """Test a reopen recovers the unsaved records and empties the log."""
+        path = str(tmp_path / "store")
+        self.write_unsaved(rust, path)
+        log = tmp_path / "store" / "wal.log"
+        assert log.stat().st_size > 0
+
+        reopened = rust.VectorStore.open(path, dimension=2)
+        assert reopened.len() == 2
+        assert reopened.get("a")["title"] == "Title A"
+        assert reopened.get("c") is None
+        assert [r["id"] for r in reopened.search([0.0, 1.0], k=1)] == ["b"]
+        assert log.stat().st_size == 0
+
+    def test_readonly_replays_without_truncating(self, rust, tmp_path):
+        """Test a read-only open sees the records but leaves the log for later."""
+        path = str(tmp_path / "store")
+        self.write_unsaved(rust, path)
+        log = tmp_path / "store" / "wal.log"
+        size = log.stat().st_size
+
+        readonly = rust.VectorStore.open(path, dimension=2, readonly=True)
+        assert readonly.len() == 2
+        assert readonly.get("b")["title"] == "Title B"
+        assert log.stat().st_size == size
+        del readonly
+
+        assert rust.VectorStore.open(path, dimension=2).len() == 2
+        assert log.stat().st_size == 0
+
+
+class TestSetBatch:
+    """Test batch inserts with vectors of the wrong dimension."""
+
+    def embed(self, contents):
+        """Embed "bad" contents to a 3-dimensional vector, the rest to 2."""
+        return [[1.0, 0.0, 0.0] if c == "bad" else [1.0, float(len(c))] for c in contents]
+
+    def insert(self, store, **kwargs):
+        """Insert a batch whose second item has a bad vector."""
+        ids = ["a", "b", "c"]
+        return store.set_batch(
+            ids,
+            ["x", "bad", "xyz"],
+            [f"Title {i}" for i in ids],
+            [f"url-{i}" for i in ids],
+            None,
+            self.embed,
+            **kwargs,
+        )
+
+    def test_skip_writes_the_valid_items(self, rust):
+        """Test on_bad_vector="skip" returns the skipped ids and stores the rest."""
+        store = rust.VectorStore(2)
+        assert self.insert(store, on_bad_vector="skip") == ["b"]
+        assert store.len() == 2
+        assert store.get("a")["title"] == "Title a"
+        assert store.get("b") is None
+        assert store.get("c")["url"] == "url-c"
+
+    def test_error_writes_nothing(self, rust):
+        """Test the default policy rejects the whole batch."""
+        store = rust.VectorStore(2)
+        with pytest.raises(ValueError):
+            self.insert(store)
+        assert store.len() == 0
+
+
+class TestReadonly:
+    """Test that a readonly store rejects every mutation."""
+
+    @pytest.fixture
+    def readonly_store(self, rust, tmp_path):
+        """A saved store with two records, reopened with readonly=True."""
+        path = str(tmp_path / "store")
+        store = rust.VectorStore.open(path, dimension=2)
+        store.set_vector("a", [1.0, 0.0], "Title A", "url-a")
+        store.set_vector("b", [0.0, 1.0], "Title B", "url-b")
+        store.soft_delete("b")
+        store.save()
+        store.close()
+        return rust.VectorStore.open(path, readonly=True)
+
+    def test_every_mutator_raises(self, readonly_store, tmp_path):
+        """Test each mutating method raises PermissionError and changes nothing."""
+        store = readonly_store
+        text_file = tmp_path / "doc.txt"
+        text_file.write_text("content")
+        embed = lambda content: [1.0, 1.0]
+        embed_all = lambda contents: [[1.0, 1.0] for _ in contents]
+        mutators = {
+            "set": lambda: store.set("c", "content", "T", "u", "", embed),
+            "set_vector": lambda: store.set_vector("c", [1.0, 1.0], "T", "u"),
+            "set_batch": lambda: store.set_batch(["c"], ["content"], ["T"], ["u"], None, embed_all),
+            "set_vectors_batch": lambda: store.set_vectors_batch(["c"], [[1.0, 1.0]], ["T"], ["u"]),
+            "set_from_file": lambda: store.set_from_file("c", str(text_file), "T", "u", embed),
+            "ingest_stream": lambda: store.ingest_stream(iter([]), embed_all),
+            "update": lambda: store.update("a", title="New"),
+            "update_many": lambda: store.update_many([{"id": "a", "title": "New"}]),
+            "update_vector": lambda: store.update_vector("a", [0.5, 0.5]),
+            "set_metadata_batch": lambda: store.set_metadata_batch([{"id": "a", "fields": {"n": 1}}]),
+            "set_sparse": lambda: store.set_sparse("a", [0], [1.0]),
+            "rm": lambda: store.rm("a"),
+            "delete_prefix": lambda: store.delete_prefix("a"),
+            "soft_delete": lambda: store.soft_delete("a"),
+            "undelete": lambda: store.undelete("b"),
+            "purge_soft_deleted": lambda: store.purge_soft_deleted(),
+            "precompute_norms": lambda: store.precompute_norms(),
+            "optimize": lambda: store.optimize(),
+            "replace_all": lambda: store.replace_all([]),
+            "transform_vectors": lambda: store.transform_vectors(lambda vectors: vectors),
+            "migrate_dimension": lambda: store.migrate_dimension(3, embed_all),
+        }
+
+        before = store.to_list(include_vectors=True)
+        for name, call in mutators.items():
+            with pytest.raises(PermissionError):
+                call()
+            assert store.to_list(include_vectors=True) == before, name
+        assert store.get("b") is None
+
+
+class TestSnapshot:
+    """Test snapshot archives restore to an identical store."""
+
+    def make_store(self, rust):
+        """A temporary store with three records."""
+        store = rust.VectorStore(2)
+        store.set_vector("a", [1.0, 0.0], "Title A", "url-a", summary="first")
+        store.set_vector("b", [0.0, 1.0], "Title B", "url-b")
+        store.set_vector("c", [0.6, 0.8], "Title C", "url-c")
+        return store
+
+    def round_trip(self, rust, tar_path):
+        """Snapshot to tar_path, restore it and compare with the original."""
+        store = self.make_store(rust)
+        store.snapshot(str(tar_path))
+
+        restored = rust.VectorStore.restore(str(tar_path), 2)
+        assert restored.len() == 3
+        assert restored.to_list(include_vectors=True) == store.to_list(include_vectors=True)
+        assert restored.search([1.0, 0.1], k=3) == store.search([1.0, 0.1], k=3)
+
+        # The two stores are independent afterwards
+        restored.rm("a")
+        assert store.get("a")["summary"] == "first"
+        return tar_path.read_bytes()
+
+    def test_tar_round_trip(self, rust, tmp_path):
+        """Test a plain .tar snapshot restores every record."""
+        data = self.round_trip(rust, tmp_path / "snap.tar")
+        assert not data.startswith(b"\x1f\x8b")
+
+    def test_tar_gz_round_trip(self, rust, tmp_path):
+        """Test a .tar.gz snapshot is gzip-compressed and restores every record."""
+        data = self.round_trip(rust, tmp_path / "snap.tar.gz")
+        assert data.startswith(b"\x1f\x8b")
+
+    def test_restore_readonly(self, rust, tmp_path):
+        """Test a snapshot can be restored as a readonly store."""
+        tar_path = str(tmp_path / "snap.tar")
+        self.make_store(rust).snapshot(tar_path)
+        restored = rust.VectorStore.restore(tar_path, 2, readonly=True)
+        assert restored.len() == 3
+        with pytest.raises(PermissionError):
+            restored.rm("a")
+
+
+class TestReplaceAll:
+    """Test that replace_all swaps the whole dataset at once."""
+
+    OLD = {"a": [1.0, 0.0], "b": [0.0, 1.0]}
+    NEW = {"x": [0.6, 0.8], "y": [0.8, 0.6], "z": [1.0, 1.0]}
+
+    def fill(self, store):
+        """Insert the OLD records."""
+        for doc_id, vector in self.OLD.items():
+            store.set_vector(doc_id, vector, f"Title {doc_id}", f"url-{doc_id}")
+
+    def records(self, data):
+        """replace_all input for a dict of id -> vector."""
+        return [{"id": i, "vector": v, "title": f"New {i}"} for i, v in data.items()]
+
+    def test_replaces_contents(self, rust):
+        """Test the old records are gone and the new ones searchable."""
+        store = rust.VectorStore(2)
+        self.fill(store)
+        assert store.replace_all(self.records(self.NEW)) == 3
+        assert sorted(r["id"] for r in store.to_list()) == ["x", "y", "z"]
+        assert store.get("a") is None
+        assert store.get("x")["title"] == "New x"
+        assert store.search([0.6, 0.8], k=1)[0]["id"] == "x"
+
+    def test_invalid_record_keeps_old_contents(self, rust):
+        """Test a bad record anywhere in the batch leaves the store untouched."""
+        store = rust.VectorStore(2)
+        self.fill(store)
+        records = self.records(self.NEW) + [{"id": "bad", "vector": [1.0, 0.0, 0.0]}]
+        with pytest.raises(ValueError):
+            store.replace_all(records)
+        assert sorted(r["id"] for r in store.to_list()) == ["a", "b"]
+
+    def test_readers_see_old_or_new(self, rust):
+        """Test concurrent searches never see a mix of old and new records."""
+        store = rust.VectorStore(2)
+        self.fill(store)
+        old, new = set(self.OLD), set(self.NEW)
+        mixed = []
+        done = threading.Event()
+
+        def reader():
+            while not done.is_set():
+                ids = {r["id"] for r in store.search([1.0, 1.0], k=5)}
+                if not (ids == old or ids == new):
+                    mixed.append(ids)
+
+        threads = [threading.Thread(target=reader) for _ in range(4)]
+        for t in threads:
+            t.start()
+        for _ in range(20):
+            store.replace_all(self.records(self.NEW))
+            store.replace_all(self.records(self.OLD))
+        done.set()
+        for t in threads:
+            t.join()
+        assert mixed == []
+
+    def test_temp_store_removes_old_directory(self, rust, tmp_path, monkeypatch):
+        """Test a temporary store's previous directory is deleted after the swap."""
+        monkeypatch.setenv("TMPDIR", str(tmp_path))
+        store = rust.VectorStore(2)
+        self.fill(store)
+        before = [p.name for p in tmp_path.iterdir()]
+        assert len(before) == 1
+
+        store.replace_all(self.records(self.NEW))
+        after = [p.name for p in tmp_path.iterdir()]
+        assert len(after) == 1 and after != before
+
+        store.close()
+        assert list(tmp_path.iterdir()) == []
+
+    def test_persistent_store_leaves_no_staging(self, rust, tmp_path):
+        """Test a persistent store is replaced in place and survives a reopen."""
+        path = str(tmp_path / "store")
+        store = rust.VectorStore.open(path, dimension=2)
+        self.fill(store)
+        store.save()
+
+        store.replace_all(self.records(self.NEW))
+        assert [p.name for p in tmp_path.iterdir()] == ["store"]
+        store.close()
+
+        reopened = rust.VectorStore.open(path)
+        assert sorted(r["id"] for r in reopened.to_list()) == ["x", "y", "z"]
+
+
+class TestSoftDelete:
+    """Test soft deletion, undeletion and purging."""
+
+    @pytest.fixture
+    def store(self, rust):
+        """A store with three records."""
+        store = rust.VectorStore(2)
+        store.set_vector("a", [1.0, 0.0], "Title A", "url-a")
+        store.set_vector("b", [0.9, 0.1], "Title B", "url-b")
+        store.set_vector("c", [0.0, 1.0], "Title C", "url-c")
+        return store
+
+    def ids(self, store):
+        """Ids returned by a search wide enough to see every record."""
+        return [r["id"] for r in store.search([1.0, 0.0], k=3)]
+
+    def test_soft_delete_hides_the_record(self, store):
+        """Test a soft-deleted record is invisible to get, search and len."""
+        assert store.soft_delete("a") is True
+        assert store.soft_delete("a") is False
+        assert store.get("a") is None
+        assert self.ids(store) == ["b", "c"]
+        assert store.len() == 2
+
+    def test_undelete_brings_it_back(self, store):
+        """Test undelete restores the record exactly."""
+        original = store.get("a")
+        store.soft_delete("a")
+        assert store.undelete("a") is True
+        assert store.undelete("a") is False
+        assert store.get("a") == original
+        assert self.ids(store) == ["a", "b", "c"]
+
+    def test_purge_respects_age(self, store):
+        """Test purge_soft_deleted only removes records deleted long enough ago."""
+        store.soft_delete("a")
+        assert store.purge_soft_deleted(older_than_secs=3600) == 0
+        assert store.undelete("a") is True
+
+        store.soft_delete("a")
+        assert store.purge_soft_deleted(older_than_secs=0) == 1
+        assert store.undelete("a") is False
+        assert store.get("a") is None
+        assert "a" not in self.ids(store)
+        assert store.len() == 2
+
+
+class TestModifiedSince:
+    """Test incremental sync through modified_since."""
+
+    def test_metadata_only_update_is_reported(self, rust):
+        """Test a record whose metadata alone changed shows up again."""
+        store = rust.VectorStore(2)
+        store.set_vector("a", [1.0, 0.0], "Title A", "url-a")
+        store.set_vector("b", [0.0, 1.0], "Title B", "url-b")
+        first = store.modified_since(0)
+        assert [r["id"] for r in first] == ["a", "b"]
+        cursor = max(r["updated_at"] for r in first)
+
+        time.sleep(0.01)
+        store.set_metadata_batch([{"id": "b", "fields": {"tag": "x"}}])
+        changed = store.modified_since(cursor)
+        assert [r["id"] for r in changed] == ["b"]
+        assert changed[0]["tag"] == "x"
+        assert changed[0]["updated_at"] > cursor
+
+    def test_every_update_path_moves_the_stamp(self, rust):
+        """Test updates, sparse vectors and undelete all count as writes."""
+        store = rust.VectorStore(2)
+        for doc_id in ["a", "b", "c", "d"]:
+            store.set_vector(doc_id, [1.0, 0.0], "Title", "url")
+        store.soft_delete("d")
+        cursor = max(r["updated_at"] for r in store.modified_since(0))
+
+        time.sleep(0.01)
+        store.update("a", title="New")
+        store.set_sparse("b", [0], [1.0])
+        store.undelete("d")
+        assert [r["id"] for r in store.modified_since(cursor)] == ["a", "b", "d"]
+
+    def test_optimize_keeps_the_stamps(self, rust):
+        """Test an index rebuild does not report every record as changed."""
+        store = rust.VectorStore(2)
+        store.set_vector("a", [1.0, 0.0], "Title A", "url-a")
+        before = store.modified_since(0)
+        time.sleep(0.01)
+        store.optimize()
+        assert store.modified_since(0) == before
+
+
+class TestChangeEvents:
+    """Test the events passed to set_on_change callbacks."""
+
+    def test_precompute_norms_reports_updates(self, rust):
+        """Test caching norms fires one update event per record."""
+        store = rust.VectorStore(2)
+        store.set_vector("a", [1.0, 0.0], "Title A", "url-a")
+        store.set_vector("b", [0.0, 1.0], "Title B", "url-b")
+        events = []
+        store.set_on_change(events.append)
+
+        assert store.precompute_norms() == 2
+        assert sorted(events, key=lambda e: e["id"]) == [
+            {"op": "update", "id": "a"},
+            {"op": "update", "id": "b"},
+        ]
+
+
 if __name__ == "__main__":
     # Run tests with pytest
     pytest.main([__file__, "-v", "--tb=short"])